@@ -1,6 +1,6 @@
 use {
     serde::Deserialize,
-    tikv_jemalloc_ctl::{epoch, stats_print},
+    tikv_jemalloc_ctl::{epoch, stats, stats_print},
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -30,6 +30,7 @@ pub struct BinStats {
     pub nmalloc: u64,
     pub ndalloc: u64,
     pub nrequests: u64,
+    pub curslabs: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -45,6 +46,7 @@ pub struct ArenaStats {
 #[derive(Debug, Deserialize)]
 pub struct BinConstants {
     pub size: u64,
+    pub nregs: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -85,6 +87,24 @@ pub fn collect_jemalloc_stats() -> Result<JemallocStats, Error> {
     Ok(global.jemalloc)
 }
 
+/// Returns the total number of bytes allocated by the application (see
+/// [`TotalStats::allocated`]).
+///
+/// Much cheaper than [`collect_jemalloc_stats`], as only a single stat is read.
+pub fn allocated_bytes() -> Result<u64, Error> {
+    epoch::advance()?;
+    Ok(stats::allocated::read()? as u64)
+}
+
+/// Returns the total number of bytes in physically resident data pages mapped
+/// by the allocator (see [`TotalStats::resident`]).
+///
+/// Much cheaper than [`collect_jemalloc_stats`], as only a single stat is read.
+pub fn resident_bytes() -> Result<u64, Error> {
+    epoch::advance()?;
+    Ok(stats::resident::read()? as u64)
+}
+
 #[cfg(feature = "metrics")]
 pub fn update_jemalloc_metrics() -> Result<(), Error> {
     use metrics::backend::gauge;
@@ -142,7 +162,108 @@ pub fn update_jemalloc_metrics() -> Result<(), Error> {
 
         gauge("jemalloc_memory_bin_nactive", active);
         gauge("jemalloc_memory_bin_nactive_size", active * bin_const.size);
+
+        // Share of the regions in the slabs currently used by the corresponding
+        // size class which aren't allocated. Zero if the size class is unused.
+        let capacity = bin_stats.curslabs * bin_const.nregs;
+        let fragmentation = if capacity > 0 {
+            (1.0 - active as f64 / capacity as f64).max(0.0)
+        } else {
+            0.0
+        };
+
+        gauge!("jemalloc_memory_bin_fragmentation", "bin_size" => bin_const.size.to_string())
+            .set(fragmentation);
     }
 
     Ok(())
 }
+
+/// Spawns a task calling [`update_jemalloc_metrics`] every `interval`.
+///
+/// The task is stopped once the returned handle is dropped.
+#[cfg(feature = "metrics")]
+pub fn spawn_jemalloc_metrics_updater(
+    interval: std::time::Duration,
+) -> future::AbortOnDropHandle<()> {
+    use future::StaticFutureExt as _;
+
+    async move {
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(err) = update_jemalloc_metrics() {
+                tracing::warn!(?err, "failed to update jemalloc metrics");
+            }
+        }
+    }
+    .spawn_abort_on_drop()
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "metrics")]
+    use {
+        super::{spawn_jemalloc_metrics_updater, update_jemalloc_metrics},
+        metrics_exporter_prometheus::PrometheusBuilder,
+        std::time::Duration,
+    };
+
+    use super::{allocated_bytes, resident_bytes};
+
+    #[global_allocator]
+    static ALLOC: crate::Jemalloc = crate::Jemalloc;
+
+    #[test]
+    fn allocated_and_resident_bytes() {
+        let data = vec![1u8; 1024 * 1024];
+
+        let allocated = allocated_bytes().unwrap();
+        assert!(allocated >= data.len() as u64, "{allocated}");
+        assert!(resident_bytes().unwrap() > 0);
+
+        drop(data);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn jemalloc_metrics_updater() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        // The task runs on the same thread using the current thread runtime.
+        let _guard = metrics::backend::set_default_local_recorder(&recorder);
+
+        let updater = spawn_jemalloc_metrics_updater(Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(updater);
+
+        let rendered = handle.render();
+        assert!(rendered.contains("jemalloc_memory_allocated"), "{rendered}");
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn bin_fragmentation() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        // Populate some of the bins.
+        let data: Vec<_> = (0..1000).map(|n| vec![0u8; n % 64 + 1]).collect();
+
+        metrics::backend::with_local_recorder(&recorder, update_jemalloc_metrics).unwrap();
+        drop(data);
+
+        let rendered = handle.render();
+        let values: Vec<f64> = rendered
+            .lines()
+            .filter(|line| line.starts_with("jemalloc_memory_bin_fragmentation{"))
+            .map(|line| line.rsplit(' ').next().unwrap().parse().unwrap())
+            .collect();
+
+        assert!(!values.is_empty(), "{rendered}");
+        assert!(values.iter().all(|v| (0.0..=1.0).contains(v)), "{values:?}");
+    }
+}
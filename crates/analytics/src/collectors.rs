@@ -1,7 +1,16 @@
 use {
-    crate::{AnalyticsEvent, Batch, BatchFactory, Collector, Exporter},
-    std::{marker::PhantomData, pin::pin, time::Duration},
-    tokio::sync::{mpsc, mpsc::error::TrySendError},
+    crate::{
+        wal::{self, WriteAheadLog},
+        AnalyticsEvent,
+        Batch,
+        BatchFactory,
+        Collector,
+        Exporter,
+        ValidationError,
+    },
+    serde::{de::DeserializeOwned, Serialize},
+    std::{convert::Infallible, io, path::PathBuf, pin::pin, sync::Arc, time::Duration},
+    tokio::sync::{mpsc, mpsc::error::TrySendError, oneshot, Semaphore},
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -23,6 +32,9 @@ pub enum CollectionError {
 
     #[error("Data channel closed")]
     DataChannelClosed,
+
+    #[error("Validation failed: {0}")]
+    Validation(#[from] ValidationError),
 }
 
 impl<T> From<TrySendError<T>> for CollectionError {
@@ -41,6 +53,11 @@ pub struct CollectorConfig {
 
     /// Maximum interval between batch data exports.
     pub export_interval: Duration,
+
+    /// Maximum number of batches being exported at the same time. Reaching
+    /// the limit stops the data collection until one of the exports finishes,
+    /// so the excess data would be dropped once the queue overflows.
+    pub max_concurrent_exports: usize,
 }
 
 impl Default for CollectorConfig {
@@ -48,12 +65,20 @@ impl Default for CollectorConfig {
         Self {
             data_queue_capacity: 8192,
             export_interval: Duration::from_secs(5 * 60),
+            max_concurrent_exports: 128,
         }
     }
 }
 
+/// Events controlling the [`EventLoop`], sent alongside the data.
+enum ControlEvent {
+    /// Export the current batch, notifying once the export is finished.
+    Flush(oneshot::Sender<()>),
+}
+
 pub struct BatchCollector<T> {
     data_tx: mpsc::Sender<T>,
+    control_tx: mpsc::Sender<ControlEvent>,
 }
 
 impl<T> BatchCollector<T>
@@ -61,22 +86,87 @@ where
     T: AnalyticsEvent,
 {
     pub fn new<B, E>(config: CollectorConfig, batch_factory: B, exporter: E) -> Self
+    where
+        B: BatchFactory<T>,
+        B::Error: std::error::Error,
+        E: Exporter,
+    {
+        Self::spawn(config, batch_factory, exporter, None)
+    }
+
+    /// Creates a collector which persists the collected records into a
+    /// write-ahead log in the `dir` directory until they're exported.
+    ///
+    /// On startup, records left over in the log (eg. after a crash or a failed
+    /// export) are exported before any new data is processed. The delivery is
+    /// at-least-once: a record may be exported twice if the process stops
+    /// after its batch was exported, but before its log segment was removed.
+    ///
+    /// Log segments of the batches which failed to export are kept and only
+    /// retried on the next startup.
+    ///
+    /// Each record is synced to the disk as it's collected, so a crash may only
+    /// lose the record being written at the time.
+    pub fn with_write_ahead_log<B, E>(
+        config: CollectorConfig,
+        batch_factory: B,
+        exporter: E,
+        dir: impl Into<PathBuf>,
+    ) -> io::Result<Self>
+    where
+        T: Serialize + DeserializeOwned,
+        B: BatchFactory<T>,
+        B::Error: std::error::Error,
+        E: Exporter,
+    {
+        let wal = WriteAheadLog::open(dir)?;
+
+        Ok(Self::spawn(config, batch_factory, exporter, Some(wal)))
+    }
+
+    fn spawn<B, E>(
+        config: CollectorConfig,
+        batch_factory: B,
+        exporter: E,
+        wal: Option<WriteAheadLog<T>>,
+    ) -> Self
     where
         B: BatchFactory<T>,
         B::Error: std::error::Error,
         E: Exporter,
     {
         let (data_tx, data_rx) = mpsc::channel(config.data_queue_capacity);
+        let (control_tx, control_rx) = mpsc::channel(16);
 
         tokio::spawn(async move {
-            let event_loop = EventLoop::new(batch_factory, exporter, config);
+            let event_loop = EventLoop::new(batch_factory, exporter, config, wal);
 
-            if let Err(err) = event_loop.run(data_rx).await {
+            if let Err(err) = event_loop.run(data_rx, control_rx).await {
                 tracing::warn!(?err, "analytics event loop failed");
             }
         });
 
-        Self { data_tx }
+        Self {
+            data_tx,
+            control_tx,
+        }
+    }
+
+    /// Exports the data collected so far without waiting for the batch to
+    /// fill up or for the export interval to pass, eg. during a graceful
+    /// shutdown.
+    ///
+    /// Returns once the export is finished. Export errors are only logged,
+    /// same as for the regular exports.
+    pub async fn flush(&self) -> Result<(), CollectionError> {
+        let (tx, rx) = oneshot::channel();
+
+        self.control_tx
+            .send(ControlEvent::Flush(tx))
+            .await
+            .map_err(|_| CollectionError::DataChannelClosed)?;
+
+        rx.await.map_err(|_| CollectionError::DataChannelClosed)
     }
 }
 
@@ -91,11 +181,23 @@ where
     }
 }
 
+/// [`Collector`] that discards all data.
+pub struct NoopCollector;
+
+impl<T> Collector<T> for NoopCollector {
+    type Error = Infallible;
+
+    fn collect(&self, _: T) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
 struct EventLoop<T, B, E> {
     batch_factory: B,
     exporter: E,
     config: CollectorConfig,
-    _marker: PhantomData<T>,
+    wal: Option<WriteAheadLog<T>>,
+    export_permits: Arc<Semaphore>,
 }
 
 impl<T, B, E> EventLoop<T, B, E>
@@ -106,16 +208,29 @@ where
     E: Exporter,
     E::Error: std::error::Error,
 {
-    fn new(batch_factory: B, exporter: E, config: CollectorConfig) -> Self {
+    fn new(
+        batch_factory: B,
+        exporter: E,
+        config: CollectorConfig,
+        wal: Option<WriteAheadLog<T>>,
+    ) -> Self {
         Self {
             batch_factory,
             exporter,
+            export_permits: Arc::new(Semaphore::new(config.max_concurrent_exports.max(1))),
             config,
-            _marker: PhantomData,
+            wal,
         }
     }
 
-    async fn run(self, data_rx: mpsc::Receiver<T>) -> Result<(), InternalError> {
+    async fn run(
+        mut self,
+        data_rx: mpsc::Receiver<T>,
+        mut control_rx: mpsc::Receiver<ControlEvent>,
+    ) -> Result<(), InternalError> {
+        // Export the data left over from the previous run before accepting new data.
+        self.recover().await?;
+
         let mut data_rx = pin!(data_rx);
         let mut export_interval = pin!(tokio::time::interval(self.config.export_interval));
 
@@ -128,38 +243,68 @@ where
             tokio::select! {
                 data = data_rx.recv() => match data {
                     Some(data) => {
-                        if let Err(err) = current_batch.push(data) {
-                            tracing::warn!(?err, "failed to push data to batch");
-
-                            // Data push error is considered transient, so try to replace the
-                            // broken batch and continue. If we can't create a new batch, exit
-                            // the event loop with an error.
-                            self.replace_batch(&mut current_batch)?;
-                            export_interval.reset();
-
-                            continue;
-                        }
-
-                        // Export the batch if it's at capacity.
-                        if current_batch.is_full() {
-                            self.export_batch(&mut current_batch)?;
+                        if self.push(&mut current_batch, data).await? {
                             export_interval.reset();
                         }
                     },
 
                     // The transmitter has been dropped. Export current batch and shutdown.
+                    // The channel only yields `None` once the buffered data is drained,
+                    // so the data collected before the drop is included.
                     None => {
-                        return self.export_batch(&mut current_batch);
+                        return self.export_batch(&mut current_batch, None).await;
                     },
                 },
 
+                Some(event) = control_rx.recv() => match event {
+                    ControlEvent::Flush(done) => {
+                        // Include the data collected before the flush was requested.
+                        while let Ok(data) = data_rx.try_recv() {
+                            self.push(&mut current_batch, data).await?;
+                        }
+
+                        self.export_batch(&mut current_batch, Some(done)).await?;
+                        export_interval.reset();
+                    }
+                },
+
                 _ = export_interval.tick() => {
-                    self.export_batch(&mut current_batch)?;
+                    self.export_batch(&mut current_batch, None).await?;
                 }
             }
         }
     }
 
+    /// Pushes the data into the current batch, exporting the batch if it's at
+    /// capacity.
+    ///
+    /// Returns whether the current batch has been replaced.
+    async fn push(&mut self, current_batch: &mut B::Batch, data: T) -> Result<bool, InternalError> {
+        self.append_to_wal(&data).await;
+
+        if let Err(err) = current_batch.push(data) {
+            tracing::warn!(?err, "failed to push data to batch");
+
+            // Data push error is considered transient, so try to replace the
+            // broken batch and continue. If we can't create a new batch, exit
+            // the event loop with an error.
+            self.replace_batch(current_batch)?;
+            if let Some(wal) = &mut self.wal {
+                wal.discard().await;
+            }
+
+            return Ok(true);
+        }
+
+        // Export the batch if it's at capacity.
+        if current_batch.is_full() {
+            self.export_batch(current_batch, None).await?;
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
     fn replace_batch(&self, current_batch: &mut B::Batch) -> Result<B::Batch, InternalError> {
         let next_batch = self
             .batch_factory
@@ -169,33 +314,135 @@ where
         Ok(std::mem::replace(current_batch, next_batch))
     }
 
-    fn export_batch(&self, current_batch: &mut B::Batch) -> Result<(), InternalError> {
+    /// Exports the current batch in the background, notifying `done` once
+    /// the export is finished.
+    ///
+    /// Waits for one of the previous exports to finish if
+    /// [`CollectorConfig::max_concurrent_exports`] is reached.
+    async fn export_batch(
+        &mut self,
+        current_batch: &mut B::Batch,
+        done: Option<oneshot::Sender<()>>,
+    ) -> Result<(), InternalError> {
+        let notify = |done: Option<oneshot::Sender<()>>| {
+            if let Some(done) = done {
+                let _ = done.send(());
+            }
+        };
+
         if current_batch.is_empty() {
+            notify(done);
             return Ok(());
         }
 
+        if self.export_permits.available_permits() == 0 {
+            tracing::warn!("too many concurrent batch exports, pausing data collection");
+        }
+
+        let permit = self
+            .export_permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|err| InternalError::Export(err.to_string()))?;
+
         let current_batch = self.replace_batch(current_batch)?;
         let exporter = self.exporter.clone();
+        let wal_segment = self.wal.as_mut().and_then(WriteAheadLog::rotate);
 
         tokio::spawn(async move {
-            let result = async {
-                let data = tokio::task::spawn_blocking(move || current_batch.serialize())
-                    .await
-                    .map_err(|_| InternalError::Serialization)?
-                    .map_err(|err| InternalError::Batch(err.to_string()))?;
-
-                exporter
-                    .export(data)
-                    .await
-                    .map_err(|err| InternalError::Export(err.to_string()))
-            }
-            .await;
+            let _permit = permit;
 
-            if let Err(err) = result {
-                tracing::warn!(?err, "failed to export batch data");
+            match export::<T, _, _>(current_batch, exporter).await {
+                Ok(()) => {
+                    if let Some(segment) = wal_segment {
+                        wal::remove_segment(&segment).await;
+                    }
+                }
+
+                Err(err) => tracing::warn!(?err, "failed to export batch data"),
             }
+
+            notify(done);
         });
 
         Ok(())
     }
+
+    async fn append_to_wal(&mut self, data: &T) {
+        if let Some(wal) = &mut self.wal {
+            if let Err(err) = wal.append(data).await {
+                tracing::warn!(?err, "failed to append data to WAL");
+            }
+        }
+    }
+
+    /// Exports the records found in the write-ahead log. Segments which failed
+    /// to export are kept to be retried on the next startup.
+    async fn recover(&mut self) -> Result<(), InternalError> {
+        let Some(wal) = &mut self.wal else {
+            return Ok(());
+        };
+
+        let segments = match wal.recover().await {
+            Ok(segments) => segments,
+            Err(err) => {
+                tracing::warn!(?err, "failed to read WAL");
+                return Ok(());
+            }
+        };
+
+        for (segment, records) in segments {
+            let mut batch = self
+                .batch_factory
+                .create()
+                .map_err(|err| InternalError::Batch(err.to_string()))?;
+            let mut exported = true;
+
+            for data in records {
+                if let Err(err) = batch.push(data) {
+                    tracing::warn!(?err, "failed to push recovered data to batch");
+                    continue;
+                }
+
+                if batch.is_full() {
+                    let full_batch = self.replace_batch(&mut batch)?;
+                    exported &= self.export_recovered(full_batch).await;
+                }
+            }
+
+            if !batch.is_empty() {
+                exported &= self.export_recovered(batch).await;
+            }
+
+            if exported {
+                wal::remove_segment(&segment).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn export_recovered(&self, batch: B::Batch) -> bool {
+        export::<T, _, _>(batch, self.exporter.clone())
+            .await
+            .map_err(|err| tracing::warn!(?err, "failed to export recovered batch data"))
+            .is_ok()
+    }
+}
+
+async fn export<T, B, E>(batch: B, exporter: E) -> Result<(), InternalError>
+where
+    B: Batch<T>,
+    E: Exporter,
+{
+    let data = tokio::task::spawn_blocking(move || batch.serialize())
+        .await
+        .map_err(|_| InternalError::Serialization)?
+        .map_err(|err| InternalError::Batch(err.to_string()))?;
+
+    exporter
+        .export(data)
+        .await
+        .map_err(|err| InternalError::Export(err.to_string()))
 }
@@ -1,12 +1,56 @@
 use {
+    crate::ParquetBatchFactory,
     async_trait::async_trait,
-    aws_sdk_s3::{operation::put_object::PutObjectError, primitives::ByteStream, Client},
-    chrono::{Datelike, Utc},
+    aws_config::{BehaviorVersion, SdkConfig},
+    aws_sdk_s3::{
+        config::{http::HttpResponse, retry::RetryConfig},
+        error::SdkError,
+        operation::put_object::PutObjectError,
+        primitives::ByteStream,
+        Client,
+    },
+    bytes::Bytes,
+    chrono::{DateTime, Datelike, Utc},
     future::FutureExt,
-    std::{convert::Infallible, net::IpAddr, time::Duration},
+    sha2::{Digest, Sha256},
+    std::{
+        convert::Infallible,
+        fmt,
+        io,
+        net::{IpAddr, Ipv4Addr, UdpSocket},
+        path::PathBuf,
+        time::Duration,
+    },
     thiserror::Error as ThisError,
+    tokio::{fs::OpenOptions, io::AsyncWriteExt},
 };
 
+/// S3 object metadata key of the [`ContentHash`] attached by the
+/// [`AwsExporter`].
+pub const CONTENT_HASH_METADATA_KEY: &str = "content-sha256";
+
+/// SHA-256 hash of the exported data, allowing downstream systems to detect
+/// duplicate uploads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentHash([u8; 32]);
+
+impl ContentHash {
+    pub fn compute(data: &[u8]) -> Self {
+        Self(Sha256::digest(data).into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// Formats the hash as a lowercase hex string.
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
 #[derive(Clone)]
 pub struct NoopExporter;
 
@@ -19,6 +63,92 @@ impl crate::Exporter for NoopExporter {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct FileConfig {
+    /// Directory the exported data files are written into.
+    pub export_dir: PathBuf,
+
+    /// Exported data file base name.
+    pub export_name: String,
+
+    /// Exported data file extension.
+    pub file_extension: String,
+}
+
+#[derive(Debug, ThisError)]
+pub enum FileError {
+    #[error("Error writing the export file: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Exporter writing the data into local files, eg. for local development.
+///
+/// Each export is written into a separate
+/// `{export_dir}/dt={date}/{export_name}_{timestamp}.{file_extension}` file,
+/// following the key naming of the [`AwsExporter`]. Files are never
+/// overwritten: an `_{n}` counter is appended to the timestamp if the file
+/// already exists.
+#[derive(Clone)]
+pub struct FileExporter {
+    config: FileConfig,
+}
+
+impl FileExporter {
+    pub fn new(config: FileConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &FileConfig {
+        &self.config
+    }
+}
+
+#[async_trait]
+impl crate::Exporter for FileExporter {
+    type Error = FileError;
+
+    async fn export(self, data: Vec<u8>) -> Result<(), Self::Error> {
+        let time = Utc::now();
+
+        let export_name = self.config.export_name;
+        let file_extension = self.config.file_extension;
+        let timestamp = time.timestamp_millis();
+
+        let dir = self.config.export_dir.join(partition(&time));
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let mut suffix = 0;
+        let (mut file, path) = loop {
+            let file_name = if suffix == 0 {
+                format!("{export_name}_{timestamp}.{file_extension}")
+            } else {
+                format!("{export_name}_{timestamp}_{suffix}.{file_extension}")
+            };
+            let path = dir.join(file_name);
+
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .await
+            {
+                Ok(file) => break (file, path),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => suffix += 1,
+                Err(err) => return Err(err.into()),
+            }
+        };
+
+        tracing::info!(path = %path.display(), "writing analytics to file");
+
+        file.write_all(&data).await?;
+        file.flush().await?;
+
+        tracing::info!("analytics successfully written");
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AwsConfig {
     /// Exported data S3 key prefix (i.e. directory).
@@ -43,6 +173,40 @@ pub struct AwsConfig {
     pub upload_timeout: Duration,
 }
 
+/// Upload timeout of the exporters created using
+/// [`AwsExporter::from_sdk_config`].
+pub const DEFAULT_UPLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Retry policy of the [`AwsExporter`] uploads.
+///
+/// Only the throttling and server (`5xx`) errors, as well as the timeouts and
+/// the connection failures are retried. Replaces the retries of the S3 client,
+/// see [`AwsExporter::with_retry`].
+#[derive(Debug, Clone, Copy)]
+pub struct AwsRetryConfig {
+    /// Maximum number of upload attempts, including the first one.
+    pub max_attempts: u32,
+
+    /// Delay before the first retry, doubled after each subsequent attempt.
+    pub base_delay: Duration,
+}
+
+impl Default for AwsRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+impl AwsRetryConfig {
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+}
+
 #[derive(Debug, ThisError)]
 pub enum AwsError {
     #[error("Error uploading to s3: {0}")]
@@ -55,11 +219,94 @@ pub enum AwsError {
 #[derive(Clone)]
 pub struct AwsExporter {
     config: AwsConfig,
+    content_hash: bool,
+    content_encoding: Option<String>,
+    retry: AwsRetryConfig,
 }
 
 impl AwsExporter {
     pub fn new(config: AwsConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            content_hash: false,
+            content_encoding: None,
+            retry: AwsRetryConfig::default(),
+        }
+    }
+
+    /// Creates an exporter of [`ParquetBatchFactory`] batches, loading the AWS
+    /// config using the SDK default provider chain (environment variables,
+    /// profile files, instance metadata etc).
+    ///
+    /// See [`AwsExporter::from_sdk_config`] for the defaults used.
+    pub async fn from_env(
+        bucket_name: impl Into<String>,
+        export_prefix: impl Into<String>,
+        export_name: impl Into<String>,
+    ) -> Self {
+        let sdk_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+        Self::from_sdk_config(&sdk_config, bucket_name, export_prefix, export_name)
+    }
+
+    /// Creates an exporter of [`ParquetBatchFactory`] batches using the
+    /// provided AWS config.
+    ///
+    /// The node address is set to the address of the interface used for the
+    /// outbound traffic, and the upload timeout to [`DEFAULT_UPLOAD_TIMEOUT`].
+    pub fn from_sdk_config(
+        sdk_config: &SdkConfig,
+        bucket_name: impl Into<String>,
+        export_prefix: impl Into<String>,
+        export_name: impl Into<String>,
+    ) -> Self {
+        Self::new(AwsConfig {
+            export_prefix: export_prefix.into(),
+            export_name: export_name.into(),
+            node_addr: outbound_addr(),
+            file_extension: ParquetBatchFactory::FILE_EXTENSION.to_owned(),
+            bucket_name: bucket_name.into(),
+            s3_client: Client::new(sdk_config),
+            upload_timeout: DEFAULT_UPLOAD_TIMEOUT,
+        })
+    }
+
+    pub fn config(&self) -> &AwsConfig {
+        &self.config
+    }
+
+    /// Attaches the [`ContentHash`] of the exported data to the S3 object
+    /// metadata under the [`CONTENT_HASH_METADATA_KEY`] key.
+    pub fn with_content_hash(mut self) -> Self {
+        self.content_hash = true;
+        self
+    }
+
+    /// Sets the `Content-Encoding` of the uploaded S3 objects, letting the
+    /// downstream consumers know how the data was compressed (eg. `gzip`).
+    pub fn with_content_encoding(mut self, content_encoding: impl Into<String>) -> Self {
+        self.content_encoding = Some(content_encoding.into());
+        self
+    }
+
+    /// Retries the failed uploads according to the provided config. The
+    /// [`AwsConfig::upload_timeout`] applies to each attempt separately.
+    ///
+    /// The retries of the [`AwsConfig::s3_client`] itself are disabled, so
+    /// they don't multiply the attempts.
+    ///
+    /// By default the uploads aren't retried by the exporter.
+    pub fn with_retry(mut self, retry: AwsRetryConfig) -> Self {
+        let s3_config = self
+            .config
+            .s3_client
+            .config()
+            .to_builder()
+            .retry_config(RetryConfig::disabled())
+            .build();
+
+        self.config.s3_client = Client::from_conf(s3_config);
+        self.retry = retry;
+        self
     }
 }
 
@@ -74,31 +321,99 @@ impl crate::Exporter for AwsExporter {
         let export_name = self.config.export_name;
         let file_extension = self.config.file_extension;
         let node_ip = &self.config.node_addr;
-        let (year, month, day) = (time.year(), time.month(), time.day());
+        let partition = partition(&time);
         let timestamp = time.timestamp_millis();
 
         let key = format!(
-            "{export_prefix}/dt={year}-{month:0>2}-{day:0>2}/{export_name}_{timestamp}_{node_ip}.\
-             {file_extension}"
+            "{export_prefix}/{partition}/{export_name}_{timestamp}_{node_ip}.{file_extension}"
         );
         let bucket = &self.config.bucket_name;
 
-        tracing::info!(bucket, key, "uploading analytics to s3");
+        let content_hash = self
+            .content_hash
+            .then(|| ContentHash::compute(&data).to_string());
 
-        self.config
-            .s3_client
-            .put_object()
-            .bucket(bucket)
-            .key(key)
-            .body(ByteStream::from(data))
-            .send()
-            .with_timeout(self.config.upload_timeout)
-            .await
-            .map_err(|_| AwsError::Timeout)?
-            .map_err(|err| AwsError::Upload(err.into_service_error()))?;
+        tracing::info!(
+            bucket,
+            key,
+            content_hash = content_hash.as_deref(),
+            "uploading analytics to s3"
+        );
+
+        let mut request = self.config.s3_client.put_object().bucket(bucket).key(&key);
+
+        if let Some(content_hash) = content_hash {
+            request = request.metadata(CONTENT_HASH_METADATA_KEY, content_hash);
+        }
+
+        if let Some(content_encoding) = self.content_encoding {
+            request = request.content_encoding(content_encoding);
+        }
+
+        let data = Bytes::from(data);
+        let max_attempts = self.retry.max_attempts.max(1);
+
+        for attempt in 1.. {
+            let result = request
+                .clone()
+                .body(ByteStream::from(data.clone()))
+                .send()
+                .with_timeout(self.config.upload_timeout)
+                .await;
+
+            let (err, retryable) = match result {
+                Ok(Ok(_)) => break,
+                Ok(Err(err)) => {
+                    let retryable = is_retryable(&err);
+                    (AwsError::Upload(err.into_service_error()), retryable)
+                }
+                Err(_) => (AwsError::Timeout, true),
+            };
+
+            if !retryable || attempt >= max_attempts {
+                return Err(err);
+            }
+
+            let delay = self.retry.delay(attempt);
+            tracing::warn!(?err, attempt, ?delay, key, "retrying analytics upload");
+            tokio::time::sleep(delay).await;
+        }
 
         tracing::info!("analytics successfully uploaded");
 
         Ok(())
     }
 }
+
+/// Whether the failed upload is worth retrying, ie. the error is transient.
+fn is_retryable(err: &SdkError<PutObjectError, HttpResponse>) -> bool {
+    match err {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) => true,
+        _ => err.raw_response().is_some_and(|response| {
+            let status = response.status();
+            status.is_server_error() || status.as_u16() == 429
+        }),
+    }
+}
+
+/// Returns the date partition (`dt=YYYY-MM-DD`) of the data exported at
+/// `time`.
+fn partition(time: &DateTime<Utc>) -> String {
+    let (year, month, day) = (time.year(), time.month(), time.day());
+    format!("dt={year}-{month:0>2}-{day:0>2}")
+}
+
+/// Returns the address of the interface used for the outbound traffic, falling
+/// back to localhost.
+fn outbound_addr() -> IpAddr {
+    // Connecting a UDP socket doesn't send anything, it only selects the route.
+    UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.connect((Ipv4Addr::new(8, 8, 8, 8), 80))?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip())
+        .ok()
+        .filter(|addr| !addr.is_loopback() && !addr.is_unspecified())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
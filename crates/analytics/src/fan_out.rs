@@ -0,0 +1,213 @@
+use {
+    crate::{Collector, Exporter},
+    async_trait::async_trait,
+    std::{error::Error as StdError, future::Future, pin::Pin, sync::Arc},
+};
+
+type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// Condition for a [`FanOutCollector::collect()`] or
+/// [`FanOutExporter::export()`] call to succeed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FanOutPolicy {
+    /// Succeed if every inner collector succeeds.
+    #[default]
+    All,
+
+    /// Succeed if at least one of the inner collectors succeeds.
+    Any,
+}
+
+/// Errors of the inner collectors that caused a [`FanOutCollector::collect()`]
+/// call to fail.
+#[derive(Debug, thiserror::Error)]
+#[error("{} of {total} collectors failed", errors.len())]
+pub struct FanOutError {
+    errors: Vec<BoxError>,
+    total: usize,
+}
+
+impl FanOutError {
+    pub fn errors(&self) -> &[BoxError] {
+        &self.errors
+    }
+}
+
+/// [`Collector`] that forwards a copy of each record to every inner collector,
+/// eg. for dual-writing into multiple analytics backends.
+pub struct FanOutCollector<T> {
+    collectors: Vec<Box<dyn DynCollector<T>>>,
+    policy: FanOutPolicy,
+}
+
+impl<T> FanOutCollector<T>
+where
+    T: Clone + 'static,
+{
+    pub fn new(policy: FanOutPolicy) -> Self {
+        Self {
+            collectors: Vec::new(),
+            policy,
+        }
+    }
+
+    /// Adds an inner collector.
+    pub fn with_collector<C>(mut self, collector: C) -> Self
+    where
+        C: Collector<T>,
+    {
+        self.collectors.push(Box::new(collector));
+        self
+    }
+}
+
+impl<T> Collector<T> for FanOutCollector<T>
+where
+    T: Clone + 'static,
+{
+    type Error = FanOutError;
+
+    fn collect(&self, data: T) -> Result<(), Self::Error> {
+        let total = self.collectors.len();
+        let mut errors = Vec::new();
+
+        if let Some((last, rest)) = self.collectors.split_last() {
+            for collector in rest {
+                if let Err(err) = collector.collect(data.clone()) {
+                    errors.push(err);
+                }
+            }
+
+            // Avoid cloning the record for the last collector.
+            if let Err(err) = last.collect(data) {
+                errors.push(err);
+            }
+        }
+
+        let failed = match self.policy {
+            FanOutPolicy::All => !errors.is_empty(),
+            FanOutPolicy::Any => total > 0 && errors.len() == total,
+        };
+
+        if failed {
+            Err(FanOutError { errors, total })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Object-safe [`Collector`] with the error type erased.
+trait DynCollector<T>: Send + Sync + 'static {
+    fn collect(&self, data: T) -> Result<(), BoxError>;
+}
+
+impl<T, C> DynCollector<T> for C
+where
+    C: Collector<T>,
+{
+    fn collect(&self, data: T) -> Result<(), BoxError> {
+        Collector::collect(self, data).map_err(Into::into)
+    }
+}
+
+/// Errors of the inner exporters that caused a [`FanOutExporter::export()`]
+/// call to fail.
+#[derive(Debug, thiserror::Error)]
+#[error("{} of {total} exporters failed", errors.len())]
+pub struct FanOutExportError {
+    errors: Vec<(usize, BoxError)>,
+    total: usize,
+}
+
+impl FanOutExportError {
+    /// Errors along with the indices of the failed exporters, in the order
+    /// they were added.
+    pub fn errors(&self) -> &[(usize, BoxError)] {
+        &self.errors
+    }
+}
+
+/// [`Exporter`] that exports a copy of the data using every inner exporter
+/// concurrently, eg. for writing into both S3 and a local file during a
+/// migration.
+#[derive(Clone)]
+pub struct FanOutExporter {
+    exporters: Vec<Arc<dyn DynExporter>>,
+    policy: FanOutPolicy,
+}
+
+impl FanOutExporter {
+    pub fn new(policy: FanOutPolicy) -> Self {
+        Self {
+            exporters: Vec::new(),
+            policy,
+        }
+    }
+
+    /// Adds an inner exporter.
+    pub fn with_exporter<E>(mut self, exporter: E) -> Self
+    where
+        E: Exporter,
+    {
+        self.exporters.push(Arc::new(exporter));
+        self
+    }
+}
+
+#[async_trait]
+impl Exporter for FanOutExporter {
+    type Error = FanOutExportError;
+
+    async fn export(self, data: Vec<u8>) -> Result<(), Self::Error> {
+        let total = self.exporters.len();
+        let mut handles = Vec::with_capacity(total);
+
+        if let Some((last, rest)) = self.exporters.split_last() {
+            for exporter in rest {
+                handles.push(tokio::spawn(exporter.export(data.clone())));
+            }
+
+            // Avoid cloning the data for the last exporter.
+            handles.push(tokio::spawn(last.export(data)));
+        }
+
+        let mut errors = Vec::new();
+
+        for (idx, handle) in handles.into_iter().enumerate() {
+            match handle.await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => errors.push((idx, err)),
+                Err(err) => errors.push((idx, err.into())),
+            }
+        }
+
+        let failed = match self.policy {
+            FanOutPolicy::All => !errors.is_empty(),
+            FanOutPolicy::Any => total > 0 && errors.len() == total,
+        };
+
+        if failed {
+            Err(FanOutExportError { errors, total })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+type ExportFuture = Pin<Box<dyn Future<Output = Result<(), BoxError>> + Send>>;
+
+/// Object-safe [`Exporter`] with the error type erased.
+trait DynExporter: Send + Sync + 'static {
+    fn export(&self, data: Vec<u8>) -> ExportFuture;
+}
+
+impl<E> DynExporter for E
+where
+    E: Exporter,
+{
+    fn export(&self, data: Vec<u8>) -> ExportFuture {
+        let exporter = self.clone();
+        Box::pin(async move { Exporter::export(exporter, data).await.map_err(Into::into) })
+    }
+}
@@ -7,15 +7,43 @@ use {
     tap::Tap,
 };
 pub use {
-    collectors::{BatchCollector, CollectionError, CollectorConfig},
-    exporters::{AwsConfig, AwsError, AwsExporter, NoopExporter},
-    serializers::{NoopBatchFactory, ParquetBatchFactory, ParquetConfig, ParquetError},
+    collectors::{BatchCollector, CollectionError, CollectorConfig, NoopCollector},
+    exporters::{
+        AwsConfig,
+        AwsError,
+        AwsExporter,
+        AwsRetryConfig,
+        ContentHash,
+        FileConfig,
+        FileError,
+        FileExporter,
+        NoopExporter,
+        CONTENT_HASH_METADATA_KEY,
+        DEFAULT_UPLOAD_TIMEOUT,
+    },
+    fan_out::{FanOutCollector, FanOutError, FanOutExportError, FanOutExporter, FanOutPolicy},
+    serializers::{
+        CsvBatch,
+        CsvBatchFactory,
+        CsvConfig,
+        CsvError,
+        NoopBatchFactory,
+        ParquetBatchFactory,
+        ParquetCodec,
+        ParquetCompression,
+        ParquetConfig,
+        ParquetError,
+    },
+    validation::{Validated, ValidationError, Validator},
 };
 
 mod collectors;
 mod exporters;
+mod fan_out;
 mod serializers;
 pub mod time;
+mod validation;
+mod wal;
 
 pub trait AnalyticsEvent: Send + Sync + 'static {}
 impl<T> AnalyticsEvent for T where T: Send + Sync + 'static {}
@@ -29,6 +57,17 @@ pub trait Exporter: Clone + Send + Sync + 'static {
 
 pub trait ExportObserver<E>: Send + Sync + 'static {
     fn observe_export(&self, _elapsed: Duration, _res: &Result<(), E>) {}
+
+    /// Whether [`ExportObserver::observe_content_hash()`] should be called.
+    ///
+    /// Disabled by default, since hashing every exported batch isn't free.
+    fn observes_content_hash(&self) -> bool {
+        false
+    }
+
+    /// Called with the [`ContentHash`] of the data before it's exported, if
+    /// [`ExportObserver::observes_content_hash()`] returns `true`.
+    fn observe_content_hash(&self, _hash: &ContentHash) {}
 }
 
 pub trait BatchFactory<T>: Send + Sync + 'static {
@@ -47,6 +86,13 @@ pub trait Batch<T>: Send + Sync + 'static {
 
     fn is_empty(&self) -> bool;
 
+    /// Number of records pushed into the batch.
+    ///
+    /// Defaults to `0` for batches which don't keep track of it.
+    fn num_rows(&self) -> usize {
+        0
+    }
+
     fn serialize(self) -> Result<Vec<u8>, Self::Error>;
 }
 
@@ -54,6 +100,10 @@ pub trait BatchObserver<T, E>: Send + Sync + 'static {
     fn observe_batch_push(&self, _res: &Result<(), E>) {}
 
     fn observe_batch_serialization(&self, _elapsed: Duration, _res: &Result<Vec<u8>, E>) {}
+
+    /// Called with the number of records in the batch and the size of the
+    /// serialized data once the batch is successfully serialized.
+    fn observe_batch_finalized(&self, _num_rows: usize, _size_bytes: usize) {}
 }
 
 pub trait Collector<T>: Send + Sync + 'static {
@@ -109,12 +159,21 @@ where
         self.inner.is_empty()
     }
 
+    fn num_rows(&self) -> usize {
+        self.inner.num_rows()
+    }
+
     fn serialize(self) -> Result<Vec<u8>, Self::Error> {
         let time = Instant::now();
+        let num_rows = self.inner.num_rows();
 
         self.inner.serialize().tap(|res| {
             self.observer
-                .observe_batch_serialization(time.elapsed(), res)
+                .observe_batch_serialization(time.elapsed(), res);
+
+            if let Ok(data) = res {
+                self.observer.observe_batch_finalized(num_rows, data.len());
+            }
         })
     }
 }
@@ -142,6 +201,11 @@ where
     type Error = I::Error;
 
     async fn export(self, data: Vec<u8>) -> Result<(), Self::Error> {
+        if self.observer.observes_content_hash() {
+            self.observer
+                .observe_content_hash(&ContentHash::compute(&data));
+        }
+
         let time = Instant::now();
 
         self.inner
@@ -165,6 +229,15 @@ pub trait AnalyticsExt {
         }
     }
 
+    /// Wraps the collector, rejecting records which fail the provided
+    /// [`Validator`] before they enter the batch.
+    fn with_validator<V>(self, validator: V) -> Validated<Self, V>
+    where
+        Self: Sized,
+    {
+        Validated::new(self, validator)
+    }
+
     fn boxed<T>(self) -> Box<dyn Collector<T, Error = Self::Error>>
     where
         Self: Collector<T> + Sized,
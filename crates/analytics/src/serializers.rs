@@ -1,14 +1,21 @@
-pub use parquet::errors::ParquetError;
+pub use {
+    self::csv::{CsvBatch, CsvBatchFactory, CsvConfig, CsvError},
+    parquet::{basic::Compression as ParquetCompression, errors::ParquetError},
+};
 use {
     crate::{AnalyticsEvent, Batch, BatchFactory},
     parquet::{
-        basic::Compression,
+        basic::{Compression, GzipLevel, ZstdLevel},
         file::{properties::WriterProperties, writer::SerializedFileWriter},
+        format::KeyValue,
         record::RecordWriter,
+        schema::{parser::parse_message_type, printer::print_schema, types::Type},
     },
     std::{convert::Infallible, sync::Arc},
 };
 
+mod csv;
+
 pub struct NoopBatchFactory;
 
 impl<T> BatchFactory<T> for NoopBatchFactory {
@@ -51,6 +58,14 @@ pub struct ParquetConfig {
     /// The data buffer initially allocated for serialization. Specifying a low
     /// value would cause memory reallocation potentially affecting performance.
     pub alloc_buffer_size: usize,
+
+    /// Compression applied to the serialized data. See
+    /// [`ParquetConfig::with_compression()`] for a validated way to set it.
+    pub compression: Compression,
+
+    /// File-level key-value metadata written into every file, eg. the schema
+    /// version or the producer name.
+    pub metadata: Vec<(String, String)>,
 }
 
 impl Default for ParquetConfig {
@@ -58,18 +73,158 @@ impl Default for ParquetConfig {
         Self {
             batch_capacity: 1024 * 128,
             alloc_buffer_size: 1024 * 1024 * 130,
+            compression: Compression::GZIP(Default::default()),
+            metadata: Vec::new(),
         }
     }
 }
 
+/// Compression codecs supported by [`ParquetConfig::with_compression()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParquetCodec {
+    /// Supports levels `0..=10`.
+    Gzip,
+
+    /// Supports levels `1..=22`.
+    Zstd,
+
+    /// Doesn't support levels.
+    Snappy,
+
+    /// Raw LZ4 block format. Doesn't support levels.
+    Lz4,
+}
+
+impl ParquetConfig {
+    /// Sets the compression codec and its level, using the codec's default
+    /// level if `level` is `None`.
+    ///
+    /// Returns an error if the level is out of the codec's supported range, or
+    /// if the codec doesn't support levels at all.
+    pub fn with_compression(
+        mut self,
+        codec: ParquetCodec,
+        level: Option<i32>,
+    ) -> Result<Self, ParquetError> {
+        let unsupported_level = || {
+            ParquetError::General(format!(
+                "{codec:?} compression doesn't support levels, got {level:?}"
+            ))
+        };
+
+        self.compression = match (codec, level) {
+            (ParquetCodec::Gzip, None) => Compression::GZIP(Default::default()),
+            (ParquetCodec::Gzip, Some(level)) => {
+                let level = u32::try_from(level).map_err(|_| {
+                    ParquetError::General(format!("Invalid Gzip compression level: {level}"))
+                })?;
+
+                Compression::GZIP(GzipLevel::try_new(level)?)
+            }
+
+            (ParquetCodec::Zstd, None) => Compression::ZSTD(Default::default()),
+            (ParquetCodec::Zstd, Some(level)) => Compression::ZSTD(ZstdLevel::try_new(level)?),
+
+            (ParquetCodec::Snappy, None) => Compression::SNAPPY,
+            (ParquetCodec::Lz4, None) => Compression::LZ4_RAW,
+            (ParquetCodec::Snappy | ParquetCodec::Lz4, Some(_)) => return Err(unsupported_level()),
+        };
+
+        Ok(self)
+    }
+
+    /// Adds file-level key-value metadata written into every file.
+    pub fn with_metadata(mut self, metadata: &[(&str, String)]) -> Self {
+        self.metadata.extend(
+            metadata
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone())),
+        );
+        self
+    }
+}
+
 pub struct ParquetBatchFactory {
     config: ParquetConfig,
 }
 
 impl ParquetBatchFactory {
+    /// File extension of the serialized batches.
+    pub const FILE_EXTENSION: &'static str = "parquet";
+
     pub fn new(config: ParquetConfig) -> Self {
         Self { config }
     }
+
+    /// Creates a factory of `T` batches, verifying that the fields of the
+    /// schema derived from `T` match the `expected` one (in the parquet message
+    /// type format).
+    ///
+    /// Field names, repetitions, physical and converted types are compared,
+    /// while the message name and logical type annotations are ignored.
+    ///
+    /// Allows detecting the drift between the record type and the declared
+    /// schema on startup, rather than when the batches are serialized.
+    pub fn with_schema<T>(config: ParquetConfig, expected: &str) -> Result<Self, ParquetError>
+    where
+        T: AnalyticsEvent,
+        [T]: RecordWriter<T>,
+    {
+        let expected = parse_message_type(expected)?;
+        let actual = ([] as [T; 0]).schema()?;
+
+        if !fields_match(&actual, &expected) {
+            return Err(ParquetError::General(format!(
+                "Schema mismatch, expected:\n{}\ngot:\n{}",
+                schema_to_string(&expected),
+                schema_to_string(&actual)
+            )));
+        }
+
+        Ok(Self::new(config))
+    }
+}
+
+fn fields_match(actual: &Type, expected: &Type) -> bool {
+    let (actual, expected) = (actual.get_fields(), expected.get_fields());
+
+    actual.len() == expected.len()
+        && actual
+            .iter()
+            .zip(expected)
+            .all(|(actual, expected)| field_matches(actual, expected))
+}
+
+fn field_matches(actual: &Type, expected: &Type) -> bool {
+    let (actual_info, expected_info) = (actual.get_basic_info(), expected.get_basic_info());
+
+    if actual_info.name() != expected_info.name()
+        || actual_info.repetition() != expected_info.repetition()
+        || actual_info.converted_type() != expected_info.converted_type()
+    {
+        return false;
+    }
+
+    match (actual, expected) {
+        (
+            Type::PrimitiveType {
+                physical_type: actual,
+                ..
+            },
+            Type::PrimitiveType {
+                physical_type: expected,
+                ..
+            },
+        ) => actual == expected,
+        (Type::GroupType { .. }, Type::GroupType { .. }) => fields_match(actual, expected),
+        _ => false,
+    }
+}
+
+fn schema_to_string(schema: &Type) -> String {
+    let mut buf = Vec::new();
+    print_schema(&mut buf, schema);
+    String::from_utf8_lossy(&buf).into_owned()
 }
 
 impl<T> BatchFactory<T> for ParquetBatchFactory
@@ -81,8 +236,17 @@ where
     type Error = ParquetError;
 
     fn create(&self) -> Result<Self::Batch, Self::Error> {
+        let metadata = (!self.config.metadata.is_empty()).then(|| {
+            self.config
+                .metadata
+                .iter()
+                .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+                .collect()
+        });
+
         let props = WriterProperties::builder()
-            .set_compression(Compression::GZIP(Default::default()))
+            .set_compression(self.config.compression)
+            .set_key_value_metadata(metadata)
             .build();
         let props = Arc::new(props);
         let schema = ([] as [T; 0]).schema()?;
@@ -125,6 +289,10 @@ where
         self.data.is_empty()
     }
 
+    fn num_rows(&self) -> usize {
+        self.data.len()
+    }
+
     fn serialize(mut self) -> Result<Vec<u8>, Self::Error> {
         let mut row_group_writer = self.writer.next_row_group()?;
 
@@ -0,0 +1,106 @@
+pub use csv::Error as CsvError;
+use {
+    crate::{AnalyticsEvent, Batch, BatchFactory},
+    csv::{Writer, WriterBuilder},
+    serde::Serialize,
+    std::marker::PhantomData,
+};
+
+#[derive(Debug, Clone)]
+pub struct CsvConfig {
+    /// The maximum number of records the batch can hold. Pushing more records
+    /// will trigger export.
+    pub batch_capacity: usize,
+
+    /// Field delimiter.
+    pub delimiter: u8,
+
+    /// Whether to write the header row with the field names at the beginning
+    /// of each batch.
+    pub has_headers: bool,
+}
+
+impl Default for CsvConfig {
+    fn default() -> Self {
+        Self {
+            batch_capacity: 1024 * 128,
+            delimiter: b',',
+            has_headers: true,
+        }
+    }
+}
+
+/// Batch factory serializing the records into CSV, one row per record.
+pub struct CsvBatchFactory {
+    config: CsvConfig,
+}
+
+impl CsvBatchFactory {
+    /// File extension of the serialized batches.
+    pub const FILE_EXTENSION: &'static str = "csv";
+
+    pub fn new(config: CsvConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl<T> BatchFactory<T> for CsvBatchFactory
+where
+    T: AnalyticsEvent + Serialize,
+{
+    type Batch = CsvBatch<T>;
+    type Error = CsvError;
+
+    fn create(&self) -> Result<Self::Batch, Self::Error> {
+        let writer = WriterBuilder::new()
+            .delimiter(self.config.delimiter)
+            .has_headers(self.config.has_headers)
+            .from_writer(Vec::new());
+
+        Ok(CsvBatch {
+            capacity: self.config.batch_capacity,
+            len: 0,
+            writer,
+            _marker: PhantomData,
+        })
+    }
+}
+
+pub struct CsvBatch<T> {
+    capacity: usize,
+    len: usize,
+    writer: Writer<Vec<u8>>,
+    _marker: PhantomData<fn(T)>,
+}
+
+impl<T> Batch<T> for CsvBatch<T>
+where
+    T: AnalyticsEvent + Serialize,
+{
+    type Error = CsvError;
+
+    fn push(&mut self, data: T) -> Result<(), Self::Error> {
+        // The header is only written along with the first record of the batch.
+        self.writer.serialize(data)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn is_full(&self) -> bool {
+        self.len >= self.capacity
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn num_rows(&self) -> usize {
+        self.len
+    }
+
+    fn serialize(self) -> Result<Vec<u8>, Self::Error> {
+        self.writer
+            .into_inner()
+            .map_err(|err| err.into_error().into())
+    }
+}
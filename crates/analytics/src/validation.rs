@@ -0,0 +1,97 @@
+use {
+    crate::{Collector, NoopCollector},
+    std::{
+        borrow::Cow,
+        sync::atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// Reason for a record being rejected by a [`Validator`].
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{0}")]
+pub struct ValidationError(Cow<'static, str>);
+
+impl ValidationError {
+    pub fn new(reason: impl Into<Cow<'static, str>>) -> Self {
+        Self(reason.into())
+    }
+}
+
+/// Checks individual records before they're accepted by a [`Collector`].
+pub trait Validator<T>: Send + Sync + 'static {
+    fn validate(&self, data: &T) -> Result<(), ValidationError>;
+}
+
+impl<T, F> Validator<T> for F
+where
+    F: Fn(&T) -> Result<(), ValidationError> + Send + Sync + 'static,
+{
+    fn validate(&self, data: &T) -> Result<(), ValidationError> {
+        self(data)
+    }
+}
+
+/// [`Collector`] wrapper that rejects records failing validation before they
+/// reach the inner collector.
+///
+/// Rejected records are counted and forwarded to the dead-letter collector,
+/// which discards them by default.
+pub struct Validated<I, V, D = NoopCollector> {
+    inner: I,
+    validator: V,
+    dead_letter: D,
+    rejected: AtomicUsize,
+}
+
+impl<I, V> Validated<I, V> {
+    pub(crate) fn new(inner: I, validator: V) -> Self {
+        Self {
+            inner,
+            validator,
+            dead_letter: NoopCollector,
+            rejected: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<I, V, D> Validated<I, V, D> {
+    /// Routes rejected records to the specified collector instead of
+    /// discarding them.
+    pub fn with_dead_letter<U>(self, dead_letter: U) -> Validated<I, V, U> {
+        Validated {
+            inner: self.inner,
+            validator: self.validator,
+            dead_letter,
+            rejected: self.rejected,
+        }
+    }
+
+    /// Returns the number of records rejected so far.
+    pub fn rejected(&self) -> usize {
+        self.rejected.load(Ordering::Relaxed)
+    }
+}
+
+impl<T, I, V, D> Collector<T> for Validated<I, V, D>
+where
+    I: Collector<T>,
+    I::Error: From<ValidationError>,
+    V: Validator<T>,
+    D: Collector<T>,
+{
+    type Error = I::Error;
+
+    fn collect(&self, data: T) -> Result<(), Self::Error> {
+        if let Err(err) = self.validator.validate(&data) {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+
+            if let Err(err) = self.dead_letter.collect(data) {
+                tracing::warn!(?err, "failed to collect rejected data");
+            }
+
+            return Err(err.into());
+        }
+
+        self.inner.collect(data)
+    }
+}
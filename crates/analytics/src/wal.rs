@@ -0,0 +1,161 @@
+use {
+    serde::{de::DeserializeOwned, Serialize},
+    std::{
+        fs,
+        io,
+        path::{Path, PathBuf},
+    },
+    tokio::{
+        fs::{File, OpenOptions},
+        io::AsyncWriteExt,
+    },
+};
+
+const SEGMENT_EXTENSION: &str = "wal";
+
+/// Write-ahead log persisting collected records until the batch containing
+/// them is exported.
+///
+/// Records of each batch are appended to a separate segment file as
+/// newline-delimited JSON. A segment is removed once its batch is exported, so
+/// any segments found on startup contain records which were never exported.
+///
+/// Each record is synced to the disk before [`WriteAheadLog::append()`]
+/// returns, so a crash may only lose the record being appended.
+pub(crate) struct WriteAheadLog<T> {
+    dir: PathBuf,
+    encode: fn(&T) -> serde_json::Result<Vec<u8>>,
+    decode: fn(&[u8]) -> serde_json::Result<T>,
+    segment: Option<(PathBuf, File)>,
+    next_segment_id: u64,
+}
+
+impl<T> WriteAheadLog<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let next_segment_id = segments(&dir)?.last().map_or(0, |(id, _)| id + 1);
+
+        Ok(Self {
+            dir,
+            encode: |data| serde_json::to_vec(data),
+            decode: |bytes| serde_json::from_slice(bytes),
+            segment: None,
+            next_segment_id,
+        })
+    }
+}
+
+impl<T> WriteAheadLog<T> {
+    /// Appends the record to the current segment, starting a new segment if
+    /// needed, and syncs it to the disk.
+    pub async fn append(&mut self, data: &T) -> io::Result<()> {
+        let mut line = (self.encode)(data)?;
+        line.push(b'\n');
+
+        if self.segment.is_none() {
+            self.segment = Some(self.create_segment().await?);
+        }
+
+        let (_, file) = self.segment.as_mut().expect("segment should be created");
+        file.write_all(&line).await?;
+        file.flush().await?;
+        file.sync_data().await
+    }
+
+    /// Closes the current segment, returning its path. Subsequent records are
+    /// going to be appended to a new segment.
+    pub fn rotate(&mut self) -> Option<PathBuf> {
+        self.segment.take().map(|(path, _)| path)
+    }
+
+    /// Closes and removes the current segment.
+    pub async fn discard(&mut self) {
+        if let Some((path, _)) = self.segment.take() {
+            remove_segment(&path).await;
+        }
+    }
+
+    /// Reads all of the existing segments, returning their paths and records.
+    ///
+    /// Records which can't be decoded (eg. partially written during a crash)
+    /// are skipped.
+    pub async fn recover(&mut self) -> io::Result<Vec<(PathBuf, Vec<T>)>> {
+        let mut recovered = Vec::new();
+
+        let dir = self.dir.clone();
+        let segments = tokio::task::spawn_blocking(move || segments(&dir))
+            .await
+            .map_err(io::Error::other)??;
+
+        for (_, path) in segments {
+            let bytes = tokio::fs::read(&path).await?;
+
+            let records = bytes
+                .split(|b| *b == b'\n')
+                .filter(|line| !line.is_empty())
+                .filter_map(|line| {
+                    (self.decode)(line)
+                        .map_err(|err| tracing::warn!(?err, ?path, "failed to decode WAL record"))
+                        .ok()
+                })
+                .collect();
+
+            recovered.push((path, records));
+        }
+
+        Ok(recovered)
+    }
+
+    async fn create_segment(&mut self) -> io::Result<(PathBuf, File)> {
+        let path = self
+            .dir
+            .join(format!("{:020}.{SEGMENT_EXTENSION}", self.next_segment_id));
+        self.next_segment_id += 1;
+
+        let file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&path)
+            .await?;
+
+        Ok((path, file))
+    }
+}
+
+/// Removes an exported segment.
+pub(crate) async fn remove_segment(path: &Path) {
+    if let Err(err) = tokio::fs::remove_file(path).await {
+        tracing::warn!(?err, ?path, "failed to remove WAL segment");
+    }
+}
+
+/// Lists the segments in the directory, ordered by their IDs.
+fn segments(dir: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.extension().and_then(|ext| ext.to_str()) != Some(SEGMENT_EXTENSION) {
+            continue;
+        }
+
+        let id = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok());
+
+        if let Some(id) = id {
+            segments.push((id, path));
+        }
+    }
+
+    segments.sort_unstable_by_key(|(id, _)| *id);
+
+    Ok(segments)
+}
@@ -1,22 +1,54 @@
 use {
     analytics::{
         AnalyticsExt,
+        AwsConfig,
+        AwsError,
+        AwsExporter,
+        AwsRetryConfig,
+        Batch,
         BatchCollector,
+        BatchFactory,
         BatchObserver,
+        CollectionError,
         CollectionObserver,
         Collector,
         CollectorConfig,
+        ContentHash,
+        CsvBatchFactory,
+        CsvConfig,
         ExportObserver,
         Exporter,
+        FanOutCollector,
+        FanOutExporter,
+        FanOutPolicy,
+        FileConfig,
+        FileExporter,
         ParquetBatchFactory,
+        ParquetCodec,
+        ParquetCompression,
         ParquetConfig,
+        ValidationError,
+        CONTENT_HASH_METADATA_KEY,
+        DEFAULT_UPLOAD_TIMEOUT,
     },
     async_trait::async_trait,
+    aws_config::SdkConfig,
+    aws_sdk_s3::{
+        config::{BehaviorVersion, Credentials, Region, SharedCredentialsProvider},
+        Client,
+    },
+    aws_smithy_runtime::client::http::test_util::{capture_request, infallible_client_fn},
+    aws_smithy_types::body::SdkBody,
+    parquet::file::reader::{FileReader, SerializedFileReader},
     parquet_derive::ParquetRecordWriter,
+    serde::{Deserialize, Serialize},
     std::{
+        convert::Infallible,
+        net::{IpAddr, Ipv4Addr},
         sync::{
             atomic::{AtomicUsize, Ordering},
             Arc,
+            Mutex,
         },
         time::Duration,
     },
@@ -40,7 +72,7 @@ impl Exporter for MockExporter {
     }
 }
 
-#[derive(ParquetRecordWriter)]
+#[derive(ParquetRecordWriter, Clone)]
 struct DataA {
     a: u32,
     b: &'static str,
@@ -59,6 +91,7 @@ async fn export_by_timeout() {
         ParquetBatchFactory::new(ParquetConfig {
             batch_capacity: 128,
             alloc_buffer_size: 8192,
+            ..Default::default()
         }),
         MockExporter(tx),
     );
@@ -94,6 +127,7 @@ async fn export_by_num_rows() {
         ParquetBatchFactory::new(ParquetConfig {
             batch_capacity: 2,
             alloc_buffer_size: 8192,
+            ..Default::default()
         }),
         MockExporter(tx),
     );
@@ -122,11 +156,51 @@ async fn export_by_num_rows() {
         .unwrap();
 }
 
+#[tokio::test]
+async fn flush() {
+    let (tx, mut rx) = mpsc::channel(32);
+
+    let collector = BatchCollector::new(
+        CollectorConfig {
+            export_interval: Duration::from_secs(60),
+            ..Default::default()
+        },
+        ParquetBatchFactory::new(ParquetConfig {
+            batch_capacity: 128,
+            alloc_buffer_size: 8192,
+            ..Default::default()
+        }),
+        MockExporter(tx),
+    );
+
+    // Nothing to export.
+    collector.flush().await.unwrap();
+    assert!(rx.try_recv().is_err());
+
+    collector
+        .collect(DataA {
+            a: 1,
+            b: "foo",
+            c: true,
+        })
+        .unwrap();
+
+    // The export is finished by the time `flush` returns.
+    tokio::time::timeout(Duration::from_millis(500), collector.flush())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(num_rows(rx.try_recv().unwrap()), 1);
+}
+
 #[derive(Default, Clone)]
 struct Observer {
     export: Arc<AtomicUsize>,
     batch_push: Arc<AtomicUsize>,
     batch_serialization: Arc<AtomicUsize>,
+    batch_rows: Arc<AtomicUsize>,
+    batch_bytes: Arc<AtomicUsize>,
     collection: Arc<AtomicUsize>,
 }
 
@@ -144,6 +218,11 @@ impl<T, E> BatchObserver<T, E> for Observer {
     fn observe_batch_serialization(&self, _: Duration, _: &Result<Vec<u8>, E>) {
         self.batch_serialization.fetch_add(1, Ordering::Relaxed);
     }
+
+    fn observe_batch_finalized(&self, num_rows: usize, size_bytes: usize) {
+        self.batch_rows.fetch_add(num_rows, Ordering::Relaxed);
+        self.batch_bytes.fetch_add(size_bytes, Ordering::Relaxed);
+    }
 }
 
 impl<T, E> CollectionObserver<T, E> for Observer {
@@ -166,6 +245,7 @@ async fn observability() {
         ParquetBatchFactory::new(ParquetConfig {
             batch_capacity: 2,
             alloc_buffer_size: 8192,
+            ..Default::default()
         })
         .with_observer(observer.clone()),
         MockExporter(tx).with_observer(observer.clone()),
@@ -190,7 +270,7 @@ async fn observability() {
 
     // Expect to receive result instantly due to row number threshold triggering
     // export.
-    tokio::time::timeout(Duration::from_millis(500), rx.recv())
+    let data = tokio::time::timeout(Duration::from_millis(500), rx.recv())
         .await
         .unwrap()
         .unwrap();
@@ -198,5 +278,779 @@ async fn observability() {
     assert_eq!(observer.export.load(Ordering::SeqCst), 1);
     assert_eq!(observer.batch_push.load(Ordering::SeqCst), 2);
     assert_eq!(observer.batch_serialization.load(Ordering::SeqCst), 1);
+    assert_eq!(observer.batch_rows.load(Ordering::SeqCst), 2);
+    assert_eq!(observer.batch_bytes.load(Ordering::SeqCst), data.len());
     assert_eq!(observer.collection.load(Ordering::SeqCst), 2);
 }
+
+#[tokio::test]
+async fn drain_on_shutdown() {
+    let (tx, mut rx) = mpsc::channel(32);
+
+    let collector = BatchCollector::new(
+        CollectorConfig {
+            export_interval: Duration::from_secs(60),
+            ..Default::default()
+        },
+        ParquetBatchFactory::new(ParquetConfig {
+            batch_capacity: 128,
+            alloc_buffer_size: 8192,
+            ..Default::default()
+        }),
+        MockExporter(tx),
+    );
+
+    for a in 0..10 {
+        collector
+            .collect(DataA {
+                a,
+                b: "foo",
+                c: true,
+            })
+            .unwrap();
+    }
+
+    // Expect the data still buffered in the channel to be exported.
+    drop(collector);
+
+    let data = tokio::time::timeout(Duration::from_millis(500), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(num_rows(data), 10);
+}
+
+#[derive(Clone, Default)]
+struct DeadLetter(Arc<Mutex<Vec<u32>>>);
+
+impl Collector<DataA> for DeadLetter {
+    type Error = Infallible;
+
+    fn collect(&self, data: DataA) -> Result<(), Self::Error> {
+        self.0.lock().unwrap().push(data.a);
+        Ok(())
+    }
+}
+
+fn num_rows(data: Vec<u8>) -> i64 {
+    SerializedFileReader::new(bytes::Bytes::from(data))
+        .unwrap()
+        .metadata()
+        .file_metadata()
+        .num_rows()
+}
+
+#[tokio::test]
+async fn validation() {
+    let (tx, mut rx) = mpsc::channel(32);
+
+    let dead_letter = DeadLetter::default();
+
+    let collector = BatchCollector::new(
+        CollectorConfig {
+            export_interval: Duration::from_millis(200),
+            ..Default::default()
+        },
+        ParquetBatchFactory::new(ParquetConfig {
+            batch_capacity: 2,
+            alloc_buffer_size: 8192,
+            ..Default::default()
+        }),
+        MockExporter(tx),
+    )
+    .with_validator(|data: &DataA| {
+        if data.a > 100 {
+            Err(ValidationError::new("value out of range"))
+        } else {
+            Ok(())
+        }
+    })
+    .with_dead_letter(dead_letter.clone());
+
+    let data = [(1, "foo"), (1000, "baz"), (2, "bar")];
+
+    for (a, b) in data {
+        let res = collector.collect(DataA { a, b, c: true });
+
+        if a > 100 {
+            assert!(matches!(res, Err(CollectionError::Validation(_))));
+        } else {
+            assert!(res.is_ok());
+        }
+    }
+
+    // Expect to receive result instantly, since the batch is filled by the valid
+    // records only.
+    let exported = tokio::time::timeout(Duration::from_millis(500), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(num_rows(exported), 2);
+    assert_eq!(collector.rejected(), 1);
+    assert_eq!(*dead_letter.0.lock().unwrap(), vec![1000]);
+
+    // Expect to receive timeout, since the rejected record never entered the
+    // batch.
+    let res = tokio::time::timeout(Duration::from_millis(500), rx.recv()).await;
+    assert!(res.is_err());
+}
+
+#[test]
+fn compression() {
+    let codecs = [
+        (ParquetCodec::Gzip, Some(9)),
+        (ParquetCodec::Zstd, Some(3)),
+        (ParquetCodec::Snappy, None),
+        (ParquetCodec::Lz4, None),
+    ];
+
+    for (codec, level) in codecs {
+        let config = ParquetConfig::default()
+            .with_compression(codec, level)
+            .unwrap();
+        let compression = config.compression;
+
+        let mut batch = BatchFactory::<DataA>::create(&ParquetBatchFactory::new(config)).unwrap();
+
+        for a in 0..10 {
+            batch
+                .push(DataA {
+                    a,
+                    b: "foo",
+                    c: true,
+                })
+                .unwrap();
+        }
+
+        let data = bytes::Bytes::from(batch.serialize().unwrap());
+        let reader = SerializedFileReader::new(data).unwrap();
+        let metadata = reader.metadata();
+
+        assert_eq!(metadata.file_metadata().num_rows(), 10);
+        // Compression levels are not stored in the file metadata, so only compare
+        // the codecs.
+        assert_eq!(
+            std::mem::discriminant(&metadata.row_group(0).column(0).compression()),
+            std::mem::discriminant(&compression)
+        );
+    }
+
+    let invalid = [
+        (ParquetCodec::Gzip, Some(11)),
+        (ParquetCodec::Gzip, Some(-1)),
+        (ParquetCodec::Zstd, Some(23)),
+        (ParquetCodec::Snappy, Some(1)),
+        (ParquetCodec::Lz4, Some(1)),
+    ];
+
+    for (codec, level) in invalid {
+        assert!(ParquetConfig::default()
+            .with_compression(codec, level)
+            .is_err());
+    }
+
+    assert!(matches!(
+        ParquetConfig::default().compression,
+        ParquetCompression::GZIP(_)
+    ));
+}
+
+#[test]
+fn metadata() {
+    let config = ParquetConfig::default().with_metadata(&[
+        ("producer", "analytics-test".to_owned()),
+        ("schema_version", "2".to_owned()),
+    ]);
+
+    let mut batch = BatchFactory::<DataA>::create(&ParquetBatchFactory::new(config)).unwrap();
+
+    batch
+        .push(DataA {
+            a: 1,
+            b: "foo",
+            c: true,
+        })
+        .unwrap();
+
+    let data = bytes::Bytes::from(batch.serialize().unwrap());
+    let reader = SerializedFileReader::new(data).unwrap();
+
+    let metadata: Vec<_> = reader
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .unwrap()
+        .iter()
+        .map(|kv| (kv.key.as_str(), kv.value.as_deref()))
+        .collect();
+
+    assert_eq!(metadata, [
+        ("producer", Some("analytics-test")),
+        ("schema_version", Some("2"))
+    ]);
+}
+
+#[derive(Clone)]
+struct FailingExporter;
+
+#[async_trait]
+impl Exporter for FailingExporter {
+    type Error = std::io::Error;
+
+    async fn export(self, _: Vec<u8>) -> Result<(), Self::Error> {
+        Err(std::io::Error::other("export failed"))
+    }
+}
+
+#[derive(ParquetRecordWriter, Serialize, Deserialize)]
+struct DataB {
+    a: u32,
+    b: String,
+}
+
+#[tokio::test]
+async fn write_ahead_log() {
+    let dir = std::env::temp_dir().join(format!("analytics_wal_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let config = || CollectorConfig {
+        export_interval: Duration::from_secs(3600),
+        ..Default::default()
+    };
+
+    let batch_factory = || {
+        ParquetBatchFactory::new(ParquetConfig {
+            batch_capacity: 128,
+            alloc_buffer_size: 8192,
+            ..Default::default()
+        })
+    };
+
+    let collector =
+        BatchCollector::with_write_ahead_log(config(), batch_factory(), FailingExporter, &dir)
+            .unwrap();
+
+    for a in 0..3 {
+        collector
+            .collect(DataB {
+                a,
+                b: a.to_string(),
+            })
+            .unwrap();
+    }
+
+    // Shutdown the collector. The export on shutdown fails, so the records should
+    // remain in the log.
+    drop(collector);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let (tx, mut rx) = mpsc::channel(32);
+
+    let _collector = BatchCollector::<DataB>::with_write_ahead_log(
+        config(),
+        batch_factory(),
+        MockExporter(tx),
+        &dir,
+    )
+    .unwrap();
+
+    // Expect to receive the logged records instantly after the restart, without
+    // collecting any new data.
+    let exported = tokio::time::timeout(Duration::from_millis(500), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(num_rows(exported), 3);
+
+    // Expect the log to be cleaned up after the successful export.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+struct ClosedCollector;
+
+impl Collector<DataA> for ClosedCollector {
+    type Error = CollectionError;
+
+    fn collect(&self, _: DataA) -> Result<(), Self::Error> {
+        Err(CollectionError::DataChannelClosed)
+    }
+}
+
+#[test]
+fn fan_out() {
+    let a = DeadLetter::default();
+    let b = DeadLetter::default();
+
+    let data = |a| DataA {
+        a,
+        b: "foo",
+        c: true,
+    };
+
+    let collector = FanOutCollector::new(FanOutPolicy::All)
+        .with_collector(a.clone())
+        .with_collector(b.clone());
+
+    collector.collect(data(1)).unwrap();
+    collector.collect(data(2)).unwrap();
+
+    // Expect every record to reach all of the inner collectors.
+    assert_eq!(*a.0.lock().unwrap(), vec![1, 2]);
+    assert_eq!(*b.0.lock().unwrap(), vec![1, 2]);
+
+    let collector = FanOutCollector::new(FanOutPolicy::All)
+        .with_collector(a.clone())
+        .with_collector(ClosedCollector);
+
+    let err = collector.collect(data(3)).unwrap_err();
+    assert_eq!(err.errors().len(), 1);
+    assert_eq!(*a.0.lock().unwrap(), vec![1, 2, 3]);
+
+    let collector = FanOutCollector::new(FanOutPolicy::Any)
+        .with_collector(a.clone())
+        .with_collector(ClosedCollector);
+
+    collector.collect(data(4)).unwrap();
+    assert_eq!(*a.0.lock().unwrap(), vec![1, 2, 3, 4]);
+
+    let collector = FanOutCollector::new(FanOutPolicy::Any)
+        .with_collector(ClosedCollector)
+        .with_collector(ClosedCollector);
+
+    assert_eq!(collector.collect(data(5)).unwrap_err().errors().len(), 2);
+}
+
+#[tokio::test]
+async fn fan_out_exporter() {
+    let (tx_a, mut rx_a) = mpsc::channel(8);
+    let (tx_b, mut rx_b) = mpsc::channel(8);
+
+    let exporter = FanOutExporter::new(FanOutPolicy::All)
+        .with_exporter(MockExporter(tx_a.clone()))
+        .with_exporter(MockExporter(tx_b));
+
+    exporter.clone().export(vec![1, 2, 3]).await.unwrap();
+
+    // Expect the data to reach all of the inner exporters.
+    assert_eq!(rx_a.recv().await.unwrap(), vec![1, 2, 3]);
+    assert_eq!(rx_b.recv().await.unwrap(), vec![1, 2, 3]);
+
+    let exporter = FanOutExporter::new(FanOutPolicy::All)
+        .with_exporter(MockExporter(tx_a.clone()))
+        .with_exporter(FailingExporter);
+
+    // The failed exporter is identified by its index.
+    let err = exporter.export(vec![4]).await.unwrap_err();
+    assert_eq!(err.errors().len(), 1);
+    assert_eq!(err.errors()[0].0, 1);
+    assert_eq!(rx_a.recv().await.unwrap(), vec![4]);
+
+    let exporter = FanOutExporter::new(FanOutPolicy::Any)
+        .with_exporter(FailingExporter)
+        .with_exporter(MockExporter(tx_a));
+
+    exporter.export(vec![5]).await.unwrap();
+    assert_eq!(rx_a.recv().await.unwrap(), vec![5]);
+
+    let exporter = FanOutExporter::new(FanOutPolicy::Any)
+        .with_exporter(FailingExporter)
+        .with_exporter(FailingExporter);
+
+    assert_eq!(
+        exporter.export(vec![6]).await.unwrap_err().errors().len(),
+        2
+    );
+}
+
+#[derive(Clone, Default)]
+struct HashObserver(Arc<Mutex<Vec<ContentHash>>>);
+
+impl<E> ExportObserver<E> for HashObserver {
+    fn observes_content_hash(&self) -> bool {
+        true
+    }
+
+    fn observe_content_hash(&self, hash: &ContentHash) {
+        self.0.lock().unwrap().push(*hash);
+    }
+}
+
+#[tokio::test]
+async fn content_hash() {
+    let (tx, mut rx) = mpsc::channel(32);
+
+    let observer = HashObserver::default();
+    let exporter = MockExporter(tx).with_observer(observer.clone());
+
+    exporter.clone().export(b"abc".to_vec()).await.unwrap();
+    exporter.clone().export(b"abc".to_vec()).await.unwrap();
+    exporter.export(b"abcd".to_vec()).await.unwrap();
+
+    let exported = rx.recv().await.unwrap();
+    let hashes = observer.0.lock().unwrap().clone();
+
+    assert_eq!(hashes.len(), 3);
+    assert_eq!(hashes[0], ContentHash::compute(&exported));
+
+    // Stable for identical content.
+    assert_eq!(hashes[0], hashes[1]);
+    assert_ne!(hashes[0], hashes[2]);
+
+    assert_eq!(
+        hashes[0].to_string(),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn aws_exporter_from_sdk_config() {
+    let credentials = Credentials::new("access_key", "secret_key", None, None, "test");
+
+    let sdk_config = SdkConfig::builder()
+        .region(Region::new("eu-central-1"))
+        .credentials_provider(SharedCredentialsProvider::new(credentials))
+        .behavior_version(BehaviorVersion::latest())
+        .build();
+
+    let exporter = AwsExporter::from_sdk_config(&sdk_config, "bucket", "prefix", "name");
+    let config = exporter.config();
+
+    assert_eq!(config.bucket_name, "bucket");
+    assert_eq!(config.export_prefix, "prefix");
+    assert_eq!(config.export_name, "name");
+    assert_eq!(config.file_extension, ParquetBatchFactory::FILE_EXTENSION);
+    assert_eq!(config.upload_timeout, DEFAULT_UPLOAD_TIMEOUT);
+    assert!(!config.node_addr.is_unspecified());
+    assert_eq!(
+        config.s3_client.config().region(),
+        Some(&Region::new("eu-central-1"))
+    );
+}
+
+/// Creates an [`AwsConfig`] using an S3 client with a mocked HTTP client
+/// provided via `s3_config`.
+fn mock_aws_config(s3_config: aws_sdk_s3::config::Builder) -> AwsConfig {
+    let s3_config = s3_config
+        .region(Region::new("eu-central-1"))
+        .credentials_provider(Credentials::new(
+            "access_key",
+            "secret_key",
+            None,
+            None,
+            "test",
+        ))
+        .behavior_version(BehaviorVersion::latest())
+        .build();
+
+    AwsConfig {
+        export_prefix: "prefix".to_owned(),
+        export_name: "name".to_owned(),
+        node_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        file_extension: ParquetBatchFactory::FILE_EXTENSION.to_owned(),
+        bucket_name: "bucket".to_owned(),
+        s3_client: Client::from_conf(s3_config),
+        upload_timeout: DEFAULT_UPLOAD_TIMEOUT,
+    }
+}
+
+#[tokio::test]
+async fn aws_exporter_content_encoding() {
+    let (http_client, request) = capture_request(None);
+
+    let config = mock_aws_config(aws_sdk_s3::Config::builder().http_client(http_client));
+
+    AwsExporter::new(config)
+        .with_content_encoding("gzip")
+        .export(vec![1, 2, 3])
+        .await
+        .unwrap();
+
+    let request = request.expect_request();
+    assert_eq!(request.headers().get("content-encoding"), Some("gzip"));
+}
+
+#[tokio::test]
+async fn aws_exporter_content_hash() {
+    let (http_client, request) = capture_request(None);
+    let config = mock_aws_config(aws_sdk_s3::Config::builder().http_client(http_client));
+
+    AwsExporter::new(config)
+        .with_content_hash()
+        .export(b"abc".to_vec())
+        .await
+        .unwrap();
+
+    let request = request.expect_request();
+    assert_eq!(
+        request
+            .headers()
+            .get(format!("x-amz-meta-{CONTENT_HASH_METADATA_KEY}")),
+        Some(ContentHash::compute(b"abc").to_string().as_str())
+    );
+}
+
+#[tokio::test]
+async fn file_exporter() {
+    let export_dir = std::env::temp_dir().join(format!("analytics_{}", std::process::id()));
+
+    let exporter = FileExporter::new(FileConfig {
+        export_dir: export_dir.clone(),
+        export_name: "name".to_owned(),
+        file_extension: ParquetBatchFactory::FILE_EXTENSION.to_owned(),
+    });
+
+    exporter.export(vec![1, 2, 3]).await.unwrap();
+
+    let partitions: Vec<_> = std::fs::read_dir(&export_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(partitions.len(), 1);
+
+    let partition = partitions[0].file_name().unwrap().to_str().unwrap();
+    assert!(partition.starts_with("dt="), "{partition}");
+
+    let files: Vec<_> = std::fs::read_dir(&partitions[0])
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 1);
+
+    let file_name = files[0].file_name().unwrap().to_str().unwrap();
+    assert!(file_name.starts_with("name_"), "{file_name}");
+    assert!(file_name.ends_with(".parquet"), "{file_name}");
+
+    assert_eq!(std::fs::read(&files[0]).unwrap(), vec![1, 2, 3]);
+
+    std::fs::remove_dir_all(export_dir).unwrap();
+}
+
+#[tokio::test]
+async fn file_exporter_unique_names() {
+    let export_dir = std::env::temp_dir().join(format!("analytics_unique_{}", std::process::id()));
+
+    let exporter = FileExporter::new(FileConfig {
+        export_dir: export_dir.clone(),
+        export_name: "name".to_owned(),
+        file_extension: ParquetBatchFactory::FILE_EXTENSION.to_owned(),
+    });
+
+    // Exports within the same millisecond must not overwrite each other.
+    for i in 0..5 {
+        exporter.clone().export(vec![i]).await.unwrap();
+    }
+
+    let mut contents: Vec<_> = std::fs::read_dir(&export_dir)
+        .unwrap()
+        .flat_map(|partition| std::fs::read_dir(partition.unwrap().path()).unwrap())
+        .map(|file| std::fs::read(file.unwrap().path()).unwrap())
+        .collect();
+    contents.sort();
+
+    assert_eq!(contents, (0..5).map(|i| vec![i]).collect::<Vec<_>>());
+
+    std::fs::remove_dir_all(export_dir).unwrap();
+}
+
+/// Creates an [`AwsExporter`] responding with the provided status codes to the
+/// consecutive upload attempts, counting the attempts.
+fn mock_aws_exporter(statuses: &'static [u16]) -> (AwsExporter, Arc<AtomicUsize>) {
+    let attempts = Arc::new(AtomicUsize::new(0));
+
+    let http_client = infallible_client_fn({
+        let attempts = attempts.clone();
+        move |_| {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            http::Response::builder()
+                .status(statuses[attempt])
+                .body(SdkBody::empty())
+                .unwrap()
+        }
+    });
+
+    let config = mock_aws_config(aws_sdk_s3::Config::builder().http_client(http_client));
+    let exporter = AwsExporter::new(config).with_retry(AwsRetryConfig {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+    });
+
+    (exporter, attempts)
+}
+
+#[tokio::test]
+async fn aws_exporter_retry() {
+    let (exporter, attempts) = mock_aws_exporter(&[503, 500, 200]);
+    exporter.export(vec![1, 2, 3]).await.unwrap();
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    // Gives up after exhausting the attempts, without the S3 client retrying
+    // each of them.
+    let (exporter, attempts) = mock_aws_exporter(&[503, 503, 503]);
+    let res = exporter.export(vec![1, 2, 3]).await;
+    assert!(matches!(res, Err(AwsError::Upload(_))), "{res:?}");
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    // Client errors aren't retried.
+    let (exporter, attempts) = mock_aws_exporter(&[403, 200]);
+    let res = exporter.export(vec![1, 2, 3]).await;
+    assert!(matches!(res, Err(AwsError::Upload(_))), "{res:?}");
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CsvRecord {
+    a: u32,
+    b: String,
+    c: bool,
+}
+
+#[tokio::test]
+async fn csv_batch() {
+    let (tx, mut rx) = mpsc::channel(32);
+
+    let collector = BatchCollector::new(
+        CollectorConfig::default(),
+        CsvBatchFactory::new(CsvConfig {
+            batch_capacity: 2,
+            delimiter: b';',
+            ..Default::default()
+        }),
+        MockExporter(tx),
+    );
+
+    let records = vec![
+        CsvRecord {
+            a: 1,
+            b: "foo".to_owned(),
+            c: true,
+        },
+        CsvRecord {
+            a: 2,
+            b: "bar;baz".to_owned(),
+            c: false,
+        },
+    ];
+
+    for record in records.clone() {
+        collector.collect(record).unwrap();
+    }
+
+    let data = tokio::time::timeout(Duration::from_millis(500), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    let text = String::from_utf8(data.clone()).unwrap();
+    assert!(text.starts_with("a;b;c\n"), "{text}");
+    assert_eq!(text.matches("a;b;c").count(), 1, "{text}");
+
+    let parsed: Vec<CsvRecord> = csv::ReaderBuilder::new()
+        .delimiter(b';')
+        .from_reader(data.as_slice())
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(parsed, records);
+}
+
+/// Exporter taking a while to export, tracking the number of concurrent
+/// exports.
+#[derive(Clone, Default)]
+struct SlowExporter {
+    current: Arc<AtomicUsize>,
+    max: Arc<AtomicUsize>,
+    finished: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Exporter for SlowExporter {
+    type Error = Infallible;
+
+    async fn export(self, _: Vec<u8>) -> Result<(), Self::Error> {
+        let current = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max.fetch_max(current, Ordering::SeqCst);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        self.current.fetch_sub(1, Ordering::SeqCst);
+        self.finished.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn max_concurrent_exports() {
+    let exporter = SlowExporter::default();
+
+    let collector = BatchCollector::new(
+        CollectorConfig {
+            max_concurrent_exports: 2,
+            ..Default::default()
+        },
+        ParquetBatchFactory::new(ParquetConfig {
+            batch_capacity: 1,
+            alloc_buffer_size: 8192,
+            ..Default::default()
+        }),
+        exporter.clone(),
+    );
+
+    for a in 0..6 {
+        collector
+            .collect(DataA {
+                a,
+                b: "foo",
+                c: true,
+            })
+            .unwrap();
+    }
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while exporter.finished.load(Ordering::SeqCst) < 6 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(exporter.max.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn parquet_schema_match() {
+    // The message name doesn't have to match the derived one.
+    let expected = "
+        message data_a {
+            REQUIRED INT32 a (UINT_32);
+            REQUIRED BINARY b (UTF8);
+            REQUIRED BOOLEAN c;
+        }
+    ";
+
+    assert!(ParquetBatchFactory::with_schema::<DataA>(ParquetConfig::default(), expected).is_ok());
+}
+
+#[test]
+fn parquet_schema_mismatch() {
+    let expected = "
+        message schema {
+            REQUIRED INT64 a;
+            REQUIRED BOOLEAN c;
+        }
+    ";
+
+    let res = ParquetBatchFactory::with_schema::<DataA>(ParquetConfig::default(), expected);
+    let err = res.err().unwrap().to_string();
+    assert!(err.contains("Schema mismatch"), "{err}");
+
+    // Invalid schemas are rejected as well.
+    let res = ParquetBatchFactory::with_schema::<DataA>(ParquetConfig::default(), "invalid");
+    assert!(res.is_err());
+}
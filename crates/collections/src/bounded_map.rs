@@ -0,0 +1,116 @@
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+};
+
+/// [`HashMap`] holding at most a fixed number of entries, evicting the least
+/// recently inserted entry when the capacity is exceeded.
+#[derive(Debug, Clone)]
+pub struct BoundedMap<K, V> {
+    map: HashMap<K, V>,
+    order: VecDeque<K>,
+    cap: usize,
+}
+
+impl<K, V> BoundedMap<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Creates a map holding at most `cap` entries. A `cap` of `0` is treated
+    /// as `1`.
+    pub fn new(cap: usize) -> Self {
+        let cap = cap.max(1);
+
+        Self {
+            map: HashMap::with_capacity(cap),
+            order: VecDeque::with_capacity(cap),
+            cap,
+        }
+    }
+
+    /// Inserts an entry, returning the entry removed from the map as a
+    /// result.
+    ///
+    /// If the map already contains the key, its value is replaced (keeping
+    /// its insertion order) and the old entry is returned. Otherwise the least
+    /// recently inserted entry is evicted and returned if the map is full.
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        if let Some(old) = self.map.get_mut(&key) {
+            let old = std::mem::replace(old, value);
+            return Some((key, old));
+        }
+
+        let evicted = if self.map.len() >= self.cap {
+            self.order
+                .pop_front()
+                .and_then(|key| self.map.remove_entry(&key))
+        } else {
+            None
+        };
+
+        self.order.push_back(key.clone());
+        self.map.insert(key, value);
+
+        evicted
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.get(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BoundedMap;
+
+    #[test]
+    fn eviction_order() {
+        let mut map = BoundedMap::new(2);
+
+        assert_eq!(map.insert("a", 1), None);
+        assert_eq!(map.insert("b", 2), None);
+        assert_eq!(map.insert("c", 3), Some(("a", 1)));
+        assert_eq!(map.insert("d", 4), Some(("b", 2)));
+
+        assert_eq!(map.get("a"), None);
+        assert_eq!(map.get("c"), Some(&3));
+        assert_eq!(map.get("d"), Some(&4));
+
+        // Replacing a value keeps the insertion order.
+        assert_eq!(map.insert("c", 5), Some(("c", 3)));
+        assert_eq!(map.insert("e", 6), Some(("c", 5)));
+        assert_eq!(map.get("d"), Some(&4));
+        assert_eq!(map.get("e"), Some(&6));
+    }
+
+    #[test]
+    fn cap() {
+        let mut map = BoundedMap::new(3);
+
+        for n in 0..100 {
+            map.insert(n, n);
+            assert!(map.len() <= 3);
+        }
+
+        assert_eq!(map.len(), 3);
+        assert!((97..100).all(|n| map.get(&n) == Some(&n)));
+
+        let mut map = BoundedMap::new(0);
+        map.insert(1, 1);
+        assert_eq!(map.insert(2, 2), Some((1, 1)));
+        assert_eq!(map.len(), 1);
+    }
+}
@@ -1,10 +1,33 @@
-use std::{collections::HashMap, hash::BuildHasher};
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::BuildHasher,
+};
+
+pub use bounded_map::BoundedMap;
+
+mod bounded_map;
 
 /// A trait to provide memory optimization functionality to [`HashMap`].
 pub trait HashMapExt {
+    /// Load factor used by [`HashMapExt::optimize`].
+    const DEFAULT_LOAD_FACTOR: f32 = 1.0 / 3.0;
+
     /// Attempts to optimize the map's memory consumption by shrinking it if the
     /// number of entries is a lot less than its capacity.
-    fn optimize(&mut self);
+    ///
+    /// Same as [`HashMapExt::optimize_with`] using the
+    /// [`HashMapExt::DEFAULT_LOAD_FACTOR`].
+    fn optimize(&mut self) {
+        self.optimize_with(Self::DEFAULT_LOAD_FACTOR);
+    }
+
+    /// Shrinks the map if the ratio of its number of entries to its capacity
+    /// is below `load_factor`.
+    ///
+    /// Higher factors save more memory at the cost of more frequent rehashing.
+    /// Factors above `1.0` are treated as `1.0`, non-positive factors disable
+    /// the shrinking.
+    fn optimize_with(&mut self, load_factor: f32);
 }
 
 impl<K, V, H> HashMapExt for HashMap<K, V, H>
@@ -13,9 +36,126 @@ where
     H: BuildHasher,
 {
     #[inline]
-    fn optimize(&mut self) {
+    fn optimize_with(&mut self, load_factor: f32) {
+        if load_factor.is_nan() || load_factor <= 0.0 {
+            return;
+        }
+
+        let threshold = self.capacity() as f64 * load_factor.min(1.0) as f64;
+
+        if (self.len() as f64) < threshold {
+            self.shrink_to_fit();
+        }
+    }
+}
+
+/// A trait to provide memory optimization functionality to [`Vec`].
+pub trait VecExt {
+    /// Shrinks the vector to fit its elements if less than a third of its
+    /// capacity is used.
+    fn shrink_if_sparse(&mut self);
+}
+
+impl<T> VecExt for Vec<T> {
+    #[inline]
+    fn shrink_if_sparse(&mut self) {
         if self.len() * 3 < self.capacity() {
             self.shrink_to_fit();
         }
     }
 }
+
+/// A trait to provide memory optimization functionality to [`VecDeque`].
+pub trait VecDequeExt {
+    /// Shrinks the deque to fit its elements if less than a third of its
+    /// capacity is used.
+    fn shrink_if_sparse(&mut self);
+}
+
+impl<T> VecDequeExt for VecDeque<T> {
+    #[inline]
+    fn shrink_if_sparse(&mut self) {
+        if self.len() * 3 < self.capacity() {
+            self.shrink_to_fit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{HashMapExt, VecDequeExt, VecExt},
+        std::collections::{HashMap, VecDeque},
+    };
+
+    fn map(len: usize, capacity: usize) -> HashMap<usize, ()> {
+        let mut map = HashMap::with_capacity(capacity);
+        map.extend((0..len).map(|n| (n, ())));
+        map
+    }
+
+    #[test]
+    fn optimize_with() {
+        let mut sparse = map(10, 1000);
+        let capacity = sparse.capacity();
+        sparse.optimize();
+        assert!(sparse.capacity() < capacity);
+        assert_eq!(sparse.len(), 10);
+
+        // Half full map is only shrunk by factors above 0.5.
+        let capacity = map(0, 1000).capacity();
+        let mut half_full = map(capacity / 2, 1000);
+        assert_eq!(half_full.capacity(), capacity);
+
+        half_full.optimize();
+        assert_eq!(half_full.capacity(), capacity);
+
+        half_full.optimize_with(0.49);
+        assert_eq!(half_full.capacity(), capacity);
+
+        half_full.optimize_with(0.51);
+        assert!(half_full.capacity() < capacity);
+    }
+
+    #[test]
+    fn optimize_with_invalid_factor() {
+        for factor in [0.0, -1.0, f32::NAN] {
+            let mut sparse = map(10, 1000);
+            let capacity = sparse.capacity();
+            sparse.optimize_with(factor);
+            assert_eq!(sparse.capacity(), capacity);
+        }
+
+        // Factors above 1.0 shrink any map with spare capacity.
+        let mut sparse = map(10, 1000);
+        let capacity = sparse.capacity();
+        sparse.optimize_with(10.0);
+        assert!(sparse.capacity() < capacity);
+    }
+
+    #[test]
+    fn vec_shrink_if_sparse() {
+        let mut vec: Vec<u8> = Vec::with_capacity(300);
+        vec.extend([0; 150]);
+        vec.shrink_if_sparse();
+        assert_eq!(vec.capacity(), 300);
+
+        vec.truncate(99);
+        vec.shrink_if_sparse();
+        assert!(vec.capacity() < 300);
+    }
+
+    #[test]
+    fn vec_deque_shrink_if_sparse() {
+        let mut deque: VecDeque<u8> = VecDeque::with_capacity(300);
+        let capacity = deque.capacity();
+        deque.extend([0; 150]);
+        deque.shrink_if_sparse();
+        assert_eq!(deque.capacity(), capacity);
+
+        deque.truncate(10);
+        deque.shrink_if_sparse();
+        assert!(deque.capacity() < capacity);
+        assert_eq!(deque.len(), 10);
+    }
+}
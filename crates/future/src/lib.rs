@@ -1,13 +1,23 @@
 pub use tokio_util::sync::CancellationToken;
+#[cfg(feature = "metrics")]
+use {
+    futures::stream::{Fuse, FuturesUnordered, Stream, StreamExt as _},
+    wc_metrics::{future::Metered, Counter, FutureExt as _, FutureMetrics, Lazy},
+};
 use {
     pin_project::pin_project,
+    rand::Rng,
     std::{
-        future::{ready, Future, Ready},
+        future::{poll_fn, ready, Future, Ready},
+        ops::{Deref, DerefMut},
         pin::Pin,
         task::{Context, Poll},
         time::Duration,
     },
-    tokio::{task::JoinHandle, time::Timeout},
+    tokio::{
+        task::{JoinError, JoinHandle},
+        time::{Instant, Timeout},
+    },
     tokio_util::sync::WaitForCancellationFutureOwned,
 };
 
@@ -43,6 +53,30 @@ where
             on_timeout,
         }
     }
+
+    /// Consumes the future, returning a new future that additionally cancels
+    /// the original future if the provided [`CancellationToken`] is canceled.
+    ///
+    /// Unlike [`FutureExt::with_cancellation()`], the errors are flattened, so
+    /// the output is either [`Error::Timeout`] or [`Error::Canceled`],
+    /// whichever happens first.
+    pub fn with_cancellation(
+        self,
+        token: CancellationToken,
+    ) -> Chained<CancellationFuture<Self, Ready<()>>> {
+        Chained {
+            fut: FutureExt::with_cancellation(self, token),
+        }
+    }
+
+    /// Additionally returns the time it took the future to complete, measured
+    /// from the first poll.
+    pub fn with_elapsed(self) -> ElapsedFuture<Self> {
+        ElapsedFuture {
+            fut: self,
+            started_at: None,
+        }
+    }
 }
 
 impl<T, U> Future for TimeoutFuture<T, U>
@@ -68,6 +102,121 @@ where
     }
 }
 
+/// Future returned by [`TimeoutFuture::with_elapsed()`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+#[pin_project]
+pub struct ElapsedFuture<T> {
+    #[pin]
+    fut: T,
+    started_at: Option<Instant>,
+}
+
+impl<T, U> Future for ElapsedFuture<T>
+where
+    T: Future<Output = Result<U, Error>>,
+{
+    type Output = Result<(U, Duration), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let started_at = *this.started_at.get_or_insert_with(Instant::now);
+
+        match this.fut.poll(cx) {
+            Poll::Ready(res) => Poll::Ready(res.map(|val| (val, started_at.elapsed()))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Future returned by [`FutureExt::with_timeout_default()`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+#[pin_project]
+pub struct TimeoutDefaultFuture<T, U> {
+    #[pin]
+    fut: T,
+    default: Option<U>,
+}
+
+impl<T, U> Future for TimeoutDefaultFuture<T, U>
+where
+    T: Future<Output = Result<U, Error>>,
+{
+    type Output = U;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        match this.fut.poll(cx) {
+            Poll::Ready(Ok(val)) => Poll::Ready(val),
+            Poll::Ready(Err(_)) => {
+                Poll::Ready(this.default.take().expect("future polled after completion"))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Future incrementing a [`Counter`] when polled. Used as an `on_timeout`
+/// future by [`FutureExt::with_timeout_metric()`].
+#[cfg(feature = "metrics")]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct IncrementCounter(&'static Lazy<Counter>);
+
+#[cfg(feature = "metrics")]
+impl Future for IncrementCounter {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.increment(1);
+        Poll::Ready(())
+    }
+}
+
+/// Stream for the [`StreamExt::buffered_unordered_metered()`] method.
+#[cfg(feature = "metrics")]
+#[must_use = "streams do nothing unless polled"]
+#[pin_project]
+pub struct BufferedUnorderedMetered<S, F, Fut> {
+    #[pin]
+    stream: Fuse<S>,
+    in_progress: FuturesUnordered<Metered<Fut>>,
+    limit: usize,
+    f: F,
+    metrics: &'static FutureMetrics,
+}
+
+#[cfg(feature = "metrics")]
+impl<S, F, Fut> Stream for BufferedUnorderedMetered<S, F, Fut>
+where
+    S: Stream,
+    F: FnMut(S::Item) -> Fut,
+    Fut: Future,
+{
+    type Item = Fut::Output;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        // Start as many futures as the limit allows.
+        while this.in_progress.len() < *this.limit {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    let fut = (this.f)(item).with_metrics(*this.metrics);
+                    this.in_progress.push(fut);
+                }
+
+                Poll::Ready(None) | Poll::Pending => break,
+            }
+        }
+
+        match this.in_progress.poll_next_unpin(cx) {
+            Poll::Ready(Some(output)) => Poll::Ready(Some(output)),
+            Poll::Ready(None) if this.stream.is_done() => Poll::Ready(None),
+            Poll::Ready(None) | Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
 #[must_use = "futures do nothing unless you `.await` or poll them"]
 #[pin_project]
 pub struct CancellationFuture<T, U = Ready<()>> {
@@ -94,6 +243,18 @@ where
             on_cancel,
         }
     }
+
+    /// Consumes the future, returning a new future that additionally times out
+    /// after the specified duration.
+    ///
+    /// Unlike [`FutureExt::with_timeout()`], the errors are flattened, so the
+    /// output is either [`Error::Canceled`] or [`Error::Timeout`], whichever
+    /// happens first.
+    pub fn with_timeout(self, duration: Duration) -> Chained<TimeoutFuture<Self, Ready<()>>> {
+        Chained {
+            fut: FutureExt::with_timeout(self, duration),
+        }
+    }
 }
 
 impl<T, U> Future for CancellationFuture<T, U>
@@ -120,6 +281,390 @@ where
     }
 }
 
+/// Chained [`TimeoutFuture`] and [`CancellationFuture`], flattening their
+/// errors.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+#[pin_project]
+pub struct Chained<T> {
+    #[pin]
+    fut: T,
+}
+
+impl<T, U> Future for Chained<T>
+where
+    T: Future<Output = Result<Result<U, Error>, Error>>,
+{
+    type Output = Result<U, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().fut.poll(cx) {
+            Poll::Ready(res) => Poll::Ready(res.and_then(|res| res)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+#[pin_project]
+pub struct GuardedFuture<T, G> {
+    #[pin]
+    fut: T,
+    guard: Option<G>,
+}
+
+impl<T, G> Future for GuardedFuture<T, G>
+where
+    T: Future,
+{
+    type Output = T::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        match this.fut.poll(cx) {
+            Poll::Ready(val) => {
+                // Release the guard as soon as the future completes.
+                this.guard.take();
+                Poll::Ready(val)
+            }
+
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// [`JoinHandle`] wrapper aborting the task when dropped. Returned by
+/// [`StaticFutureExt::spawn_abort_on_drop()`].
+#[must_use = "the task is aborted when the handle is dropped"]
+#[derive(Debug)]
+pub struct AbortOnDropHandle<T>(JoinHandle<T>);
+
+impl<T> Future for AbortOnDropHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+impl<T> Deref for AbortOnDropHandle<T> {
+    type Target = JoinHandle<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for AbortOnDropHandle<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> Drop for AbortOnDropHandle<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Output of [`race2()`], holding the value of the future that completed
+/// first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Race2Output<A, B> {
+    First(A),
+    Second(B),
+}
+
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+#[pin_project]
+pub struct Race2<A, B> {
+    #[pin]
+    a: Option<A>,
+    #[pin]
+    b: Option<B>,
+}
+
+impl<A, B> Future for Race2<A, B>
+where
+    A: Future,
+    B: Future,
+{
+    type Output = Race2Output<A::Output, B::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        let output = match (this.a.as_mut().as_pin_mut(), this.b.as_mut().as_pin_mut()) {
+            (Some(a), Some(b)) => match a.poll(cx) {
+                Poll::Ready(val) => Race2Output::First(val),
+
+                Poll::Pending => match b.poll(cx) {
+                    Poll::Ready(val) => Race2Output::Second(val),
+                    Poll::Pending => return Poll::Pending,
+                },
+            },
+
+            _ => panic!("`Race2` polled after completion"),
+        };
+
+        // Drop the loser right away, instead of waiting for `Race2` itself to be
+        // dropped.
+        this.a.set(None);
+        this.b.set(None);
+
+        Poll::Ready(output)
+    }
+}
+
+/// Races two futures, returning the output of the one that completes first
+/// and dropping the other one.
+///
+/// If both futures are ready on the same poll, the first one wins.
+///
+/// # Example
+///
+/// ```rust
+/// use {
+///     future::{race2, Race2Output},
+///     std::time::Duration,
+/// };
+///
+/// # async fn example() {
+/// let fast = async { 42 };
+/// let slow = async {
+///     tokio::time::sleep(Duration::from_millis(500)).await;
+///     "slow"
+/// };
+///
+/// assert_eq!(race2(slow, fast).await, Race2Output::Second(42));
+/// # }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// #     example().await;
+/// # }
+/// ```
+pub fn race2<A, B>(a: A, b: B) -> Race2<A, B>
+where
+    A: Future,
+    B: Future,
+{
+    Race2 {
+        a: Some(a),
+        b: Some(b),
+    }
+}
+
+/// Runs the blocking closure using [`tokio::task::spawn_blocking()`],
+/// resolving to [`Error::Canceled`] if the provided [`CancellationToken`] is
+/// canceled before the closure finishes.
+///
+/// The closure can't be interrupted, so it still runs to completion in case of
+/// cancellation, but its result is discarded. Panics of the closure are
+/// propagated to the caller. Resolves to [`Error::Canceled`] as well if the
+/// runtime shuts down before the closure starts.
+///
+/// # Example
+///
+/// ```rust
+/// use {
+///     future::{spawn_blocking_cancellable, Error},
+///     std::time::Duration,
+///     tokio_util::sync::CancellationToken,
+/// };
+///
+/// # async fn example() {
+/// let token = CancellationToken::new();
+/// token.cancel();
+///
+/// let answer = spawn_blocking_cancellable(
+///     || {
+///         std::thread::sleep(Duration::from_millis(100));
+///         42
+///     },
+///     token,
+/// );
+///
+/// assert!(matches!(answer.await, Err(Error::Canceled)));
+/// # }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// #     example().await;
+/// # }
+/// ```
+pub async fn spawn_blocking_cancellable<F, T>(f: F, token: CancellationToken) -> Result<T, Error>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f)
+        .with_cancellation(token)
+        .await
+    {
+        Ok(Ok(val)) => Ok(val),
+
+        // The task is never aborted, but it may still be canceled by the runtime
+        // shutting down before it starts.
+        Ok(Err(err)) if err.is_cancelled() => Err(Error::Canceled),
+        Ok(Err(err)) => std::panic::resume_unwind(err.into_panic()),
+
+        Err(err) => Err(err),
+    }
+}
+
+/// Awaits the futures concurrently, canceling the provided
+/// [`CancellationToken`] and dropping the rest of the futures as soon as one of
+/// them fails.
+///
+/// Resolves to the outputs in the order of the futures if all of them
+/// succeed, or to the first error otherwise. Resolves to [`Error::Canceled`]
+/// if the token is canceled elsewhere before any of the futures fails, which
+/// allows sharing the token with the tasks spawned by the futures.
+///
+/// All of the pending futures are polled on each wakeup, so it's not meant for
+/// large numbers of futures.
+///
+/// # Example
+///
+/// ```rust
+/// use {
+///     future::{join_all_cancellable, CancellationToken},
+///     std::time::Duration,
+/// };
+///
+/// # async fn example() {
+/// let token = CancellationToken::new();
+///
+/// let task = |millis, res| async move {
+///     tokio::time::sleep(Duration::from_millis(millis)).await;
+///     res
+/// };
+///
+/// let res = join_all_cancellable(
+///     [task(500, Ok(1)), task(100, Err("failed")), task(500, Ok(3))],
+///     token.clone(),
+/// );
+///
+/// // The slow futures are canceled once the second one fails.
+/// assert_eq!(res.await, Ok(Err("failed")));
+/// assert!(token.is_cancelled());
+/// # }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// #     example().await;
+/// # }
+/// ```
+pub async fn join_all_cancellable<I, T, E>(
+    futures: I,
+    token: CancellationToken,
+) -> Result<Result<Vec<T>, E>, Error>
+where
+    I: IntoIterator,
+    I::Item: Future<Output = Result<T, E>>,
+{
+    let mut futures = futures
+        .into_iter()
+        .map(|fut| Some(Box::pin(fut.with_cancellation(token.clone()))))
+        .collect::<Vec<_>>();
+    let mut outputs = futures.iter().map(|_| None).collect::<Vec<_>>();
+
+    let res = poll_fn(|cx| {
+        let mut is_pending = false;
+
+        for (slot, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            let Some(fut) = slot else {
+                continue;
+            };
+
+            match fut.as_mut().poll(cx) {
+                Poll::Ready(Ok(Ok(val))) => {
+                    *output = Some(val);
+                    *slot = None;
+                }
+
+                Poll::Ready(Ok(Err(err))) => return Poll::Ready(Ok(Err(err))),
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => is_pending = true,
+            }
+        }
+
+        if is_pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(Ok(())))
+        }
+    })
+    .await;
+
+    if let Ok(Err(_)) = res {
+        token.cancel();
+    }
+
+    res.map(|res| res.map(|()| outputs.into_iter().flatten().collect()))
+}
+
+/// Parses a client-specified deadline header value (eg. the value of
+/// `X-Request-Deadline-Ms`) containing the number of milliseconds the client is
+/// willing to wait, and returns the resulting deadline.
+///
+/// Falls back to the `default` timeout if the header is missing or invalid.
+///
+/// # Example
+///
+/// ```rust
+/// use {
+///     future::{deadline_from_header, Error, FutureExt},
+///     std::time::Duration,
+/// };
+///
+/// # async fn example() {
+/// let deadline = deadline_from_header(Some("100"), Duration::from_secs(5));
+///
+/// let answer = async {
+///     tokio::time::sleep(Duration::from_millis(500)).await;
+///     42
+/// }
+/// .with_timeout_at(deadline);
+///
+/// // Did not receive the answer within the client-specified 100ms.
+/// assert!(matches!(answer.await, Err(Error::Timeout)));
+/// # }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// #     example().await;
+/// # }
+/// ```
+pub fn deadline_from_header<V>(value: Option<V>, default: Duration) -> Instant
+where
+    V: AsRef<[u8]>,
+{
+    let timeout = value
+        .and_then(|value| {
+            std::str::from_utf8(value.as_ref())
+                .ok()?
+                .trim()
+                .parse()
+                .ok()
+        })
+        .map(Duration::from_millis)
+        .unwrap_or(default);
+
+    Instant::now() + timeout
+}
+
+/// Picks a random duration within `base ± jitter`, with `jitter` capped at
+/// `base` to keep the distribution centered around `base`.
+fn jittered(base: Duration, jitter: Duration) -> Duration {
+    let jitter = jitter.min(base);
+    let max_offset = jitter.saturating_mul(2).as_nanos().min(u64::MAX as u128) as u64;
+    let offset = Duration::from_nanos(rand::thread_rng().gen_range(0..=max_offset));
+
+    base.saturating_sub(jitter).saturating_add(offset)
+}
+
 /// Quality of life methods for cleaner futures spawning, timeout and
 /// cancellation using [`CancellationToken`].
 pub trait FutureExt {
@@ -158,6 +703,95 @@ pub trait FutureExt {
     /// ```
     fn with_timeout(self, duration: Duration) -> TimeoutFuture<Self::Future, Ready<()>>;
 
+    /// Same as [`FutureExt::with_timeout()`], but expires at the specified
+    /// `deadline` instead, effectively wrapping the future in
+    /// [`tokio::time::timeout_at()`].
+    ///
+    /// See [`deadline_from_header()`] for an example.
+    fn with_timeout_at(self, deadline: Instant) -> TimeoutFuture<Self::Future, Ready<()>>;
+
+    /// Same as [`FutureExt::with_timeout()`], but the timeout duration is
+    /// randomly picked within `base ± jitter` for each future. The `jitter`
+    /// is capped at `base`.
+    ///
+    /// Prevents the timeouts of the futures started at the same time from
+    /// firing simultaneously, causing correlated retries.
+    fn with_jittered_timeout(
+        self,
+        base: Duration,
+        jitter: Duration,
+    ) -> TimeoutFuture<Self::Future, Ready<()>>;
+
+    /// Same as [`FutureExt::with_timeout()`], but resolves to the provided
+    /// `default` value instead of an error if the timeout expires.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use {future::FutureExt, std::time::Duration};
+    ///
+    /// # async fn example() {
+    /// let answer = async {
+    ///     tokio::time::sleep(Duration::from_millis(500)).await;
+    ///     42
+    /// }
+    /// .with_timeout_default(Duration::from_millis(100), 0);
+    ///
+    /// // Did not receive the answer within 100ms, falling back to the default.
+    /// assert_eq!(answer.await, 0);
+    /// # }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #     example().await;
+    /// # }
+    /// ```
+    fn with_timeout_default(
+        self,
+        duration: Duration,
+        default: <Self::Future as Future>::Output,
+    ) -> TimeoutDefaultFuture<
+        TimeoutFuture<Self::Future, Ready<()>>,
+        <Self::Future as Future>::Output,
+    >;
+
+    /// Same as [`FutureExt::with_timeout()`], but also increments the provided
+    /// counter if the timeout expires.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use {
+    ///     future::{Error, FutureExt},
+    ///     std::time::Duration,
+    ///     wc_metrics::{self as metrics, Counter, Lazy},
+    /// };
+    ///
+    /// static TIMEOUTS: Lazy<Counter> = metrics::new("timeouts");
+    ///
+    /// # async fn example() {
+    /// let answer = async {
+    ///     tokio::time::sleep(Duration::from_millis(500)).await;
+    ///     42
+    /// }
+    /// .with_timeout_metric(Duration::from_millis(100), &TIMEOUTS);
+    ///
+    /// // Did not receive the answer within 100ms, `TIMEOUTS` is incremented.
+    /// assert!(matches!(answer.await, Err(Error::Timeout)));
+    /// # }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #     example().await;
+    /// # }
+    /// ```
+    #[cfg(feature = "metrics")]
+    fn with_timeout_metric(
+        self,
+        duration: Duration,
+        counter: &'static Lazy<Counter>,
+    ) -> TimeoutFuture<Self::Future, IncrementCounter>;
+
     /// Consumes the future, returning a new future that cancels the original
     /// future if the provided [`CancellationToken`] is canceled. Optionally
     /// allows to run another future in case of cancellation.
@@ -202,6 +836,39 @@ pub trait FutureExt {
         self,
         token: CancellationToken,
     ) -> CancellationFuture<Self::Future, Ready<()>>;
+
+    /// Consumes the future, returning a new future that holds the provided
+    /// guard until the original future either completes or is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use {
+    ///     future::{CancellationToken, FutureExt},
+    ///     std::time::Duration,
+    /// };
+    ///
+    /// # async fn example() {
+    /// let token = CancellationToken::new();
+    ///
+    /// let answer = async {
+    ///     tokio::time::sleep(Duration::from_millis(100)).await;
+    ///     42
+    /// }
+    /// .guarded(token.clone().drop_guard());
+    ///
+    /// // The guard is held while the future is running.
+    /// assert!(!token.is_cancelled());
+    /// assert_eq!(answer.await, 42);
+    /// assert!(token.is_cancelled());
+    /// # }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #     example().await;
+    /// # }
+    /// ```
+    fn guarded<G>(self, guard: G) -> GuardedFuture<Self::Future, G>;
 }
 
 pub trait StaticFutureExt {
@@ -231,6 +898,32 @@ pub trait StaticFutureExt {
     /// # }
     /// ```
     fn spawn(self) -> JoinHandle<<Self::Future as Future>::Output>;
+
+    /// Same as [`StaticFutureExt::spawn()`], but the task is aborted once the
+    /// returned handle is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use {future::StaticFutureExt, std::time::Duration};
+    ///
+    /// # async fn example() {
+    /// let handle = async {
+    ///     tokio::time::sleep(Duration::from_millis(500)).await;
+    ///     42
+    /// }
+    /// .spawn_abort_on_drop();
+    ///
+    /// // The task is aborted if the handle is dropped before it completes.
+    /// assert!(matches!(handle.await, Ok(42)));
+    /// # }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #     example().await;
+    /// # }
+    /// ```
+    fn spawn_abort_on_drop(self) -> AbortOnDropHandle<<Self::Future as Future>::Output>;
 }
 
 impl<T> FutureExt for T
@@ -246,6 +939,42 @@ where
         }
     }
 
+    fn with_timeout_at(self, deadline: Instant) -> TimeoutFuture<Self::Future, Ready<()>> {
+        TimeoutFuture {
+            fut: tokio::time::timeout_at(deadline, self),
+            on_timeout: ready(()),
+        }
+    }
+
+    fn with_jittered_timeout(
+        self,
+        base: Duration,
+        jitter: Duration,
+    ) -> TimeoutFuture<Self::Future, Ready<()>> {
+        self.with_timeout(jittered(base, jitter))
+    }
+
+    fn with_timeout_default(
+        self,
+        duration: Duration,
+        default: T::Output,
+    ) -> TimeoutDefaultFuture<TimeoutFuture<Self::Future, Ready<()>>, T::Output> {
+        TimeoutDefaultFuture {
+            fut: self.with_timeout(duration),
+            default: Some(default),
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn with_timeout_metric(
+        self,
+        duration: Duration,
+        counter: &'static Lazy<Counter>,
+    ) -> TimeoutFuture<Self::Future, IncrementCounter> {
+        self.with_timeout(duration)
+            .on_timeout(IncrementCounter(counter))
+    }
+
     fn with_cancellation(
         self,
         token: CancellationToken,
@@ -256,6 +985,13 @@ where
             on_cancel: ready(()),
         }
     }
+
+    fn guarded<G>(self, guard: G) -> GuardedFuture<Self::Future, G> {
+        GuardedFuture {
+            fut: self,
+            guard: Some(guard),
+        }
+    }
 }
 
 impl<T> StaticFutureExt for T
@@ -268,15 +1004,76 @@ where
     fn spawn(self) -> JoinHandle<<Self::Future as Future>::Output> {
         tokio::spawn(self)
     }
+
+    fn spawn_abort_on_drop(self) -> AbortOnDropHandle<<Self::Future as Future>::Output> {
+        AbortOnDropHandle(self.spawn())
+    }
+}
+
+#[cfg(feature = "metrics")]
+pub trait StreamExt: Stream + Sized {
+    /// Consumes the stream, returning a new stream that runs `f` over the items
+    /// of the original stream with at most `limit` futures in progress at
+    /// once. Each future is instrumented with the provided [`FutureMetrics`].
+    ///
+    /// The outputs are yielded in the order the futures complete. A `limit` of
+    /// zero is treated as one.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use {
+    ///     future::StreamExt as _,
+    ///     futures::stream::{self, StreamExt as _},
+    ///     wc_metrics::{self as metrics, FutureMetrics, Lazy},
+    /// };
+    ///
+    /// static FETCH_METRICS: Lazy<FutureMetrics> = metrics::new("fetch");
+    ///
+    /// # async fn example() {
+    /// let sum = stream::iter(0..10)
+    ///     .buffered_unordered_metered(4, &FETCH_METRICS, |n| async move { n * 2 })
+    ///     .fold(0, |acc, n| async move { acc + n })
+    ///     .await;
+    ///
+    /// assert_eq!(sum, 90);
+    /// # }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #     example().await;
+    /// # }
+    /// ```
+    fn buffered_unordered_metered<F, Fut>(
+        self,
+        limit: usize,
+        metrics: impl Into<&'static FutureMetrics>,
+        f: F,
+    ) -> BufferedUnorderedMetered<Self, F, Fut>
+    where
+        F: FnMut(Self::Item) -> Fut,
+        Fut: Future,
+    {
+        BufferedUnorderedMetered {
+            stream: self.fuse(),
+            in_progress: FuturesUnordered::new(),
+            limit: limit.max(1),
+            f,
+            metrics: metrics.into(),
+        }
+    }
 }
 
+#[cfg(feature = "metrics")]
+impl<S> StreamExt for S where S: Stream {}
+
 #[cfg(test)]
 mod test {
     use {
         super::*,
         std::{
             sync::{
-                atomic::{AtomicU32, Ordering},
+                atomic::{AtomicBool, AtomicU32, Ordering},
                 Arc,
             },
             time::Duration,
@@ -284,6 +1081,14 @@ mod test {
         tokio_util::sync::CancellationToken,
     };
 
+    struct SetOnDrop(Arc<AtomicBool>);
+
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
     #[tokio::test]
     async fn cancel() {
         let a = Arc::new(AtomicU32::default());
@@ -397,4 +1202,413 @@ mod test {
         assert_eq!(a.load(Ordering::SeqCst), 2);
         assert_eq!(b.load(Ordering::SeqCst), 0);
     }
+
+    #[tokio::test]
+    async fn deadline() {
+        let sleep = || async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            42
+        };
+
+        // The client-specified deadline is honored.
+        let started = Instant::now();
+        let deadline = deadline_from_header(Some(" 100 "), Duration::from_secs(5));
+        assert_eq!(sleep().with_timeout_at(deadline).await, Err(Error::Timeout));
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert!(started.elapsed() < Duration::from_millis(400));
+
+        // Missing or invalid header values fall back to the default.
+        let default = Duration::from_millis(1000);
+
+        for value in [None, Some("abc"), Some("-1"), Some("")] {
+            let deadline = deadline_from_header(value, default);
+            assert!(deadline > Instant::now() + Duration::from_millis(900));
+            assert_eq!(sleep().with_timeout_at(deadline).await, Ok(42));
+        }
+    }
+
+    #[tokio::test]
+    async fn jittered_timeout() {
+        let base = Duration::from_millis(200);
+        let jitter = Duration::from_millis(100);
+
+        let durations: Vec<_> = (0..10).map(|_| jittered(base, jitter)).collect();
+
+        assert!(durations
+            .iter()
+            .all(|d| *d >= base - jitter && *d <= base + jitter));
+
+        // Futures with the same base get different timeouts.
+        assert!(durations.iter().any(|d| *d != durations[0]));
+
+        // Jitter larger than the base is capped, rather than skewing the
+        // timeouts upwards.
+        assert!((0..10)
+            .map(|_| jittered(base, base * 3))
+            .all(|d| d <= base * 2));
+
+        let started = Instant::now();
+        let res = tokio::time::sleep(Duration::from_millis(500))
+            .with_jittered_timeout(base, jitter)
+            .await;
+
+        assert_eq!(res, Err(Error::Timeout));
+        assert!(started.elapsed() >= base - jitter);
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn deadline_in_past() {
+        let timed_out = Arc::new(AtomicBool::new(false));
+
+        let started = Instant::now();
+        let res = async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            42
+        }
+        .with_timeout_at(started - Duration::from_millis(100))
+        .on_timeout({
+            let timed_out = timed_out.clone();
+            async move { timed_out.store(true, Ordering::SeqCst) }
+        })
+        .await;
+
+        assert_eq!(res, Err(Error::Timeout));
+        assert!(timed_out.load(Ordering::SeqCst));
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn elapsed() {
+        let res = tokio::time::sleep(Duration::from_millis(100))
+            .with_timeout(Duration::from_millis(500))
+            .with_elapsed()
+            .await;
+
+        let ((), elapsed) = res.unwrap();
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_millis(400));
+
+        let res = tokio::time::sleep(Duration::from_millis(500))
+            .with_timeout(Duration::from_millis(100))
+            .with_elapsed()
+            .await;
+
+        assert_eq!(res, Err(Error::Timeout));
+    }
+
+    #[tokio::test]
+    async fn timeout_default() {
+        let sleep = |millis| async move {
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+            42
+        };
+
+        let res = sleep(500)
+            .with_timeout_default(Duration::from_millis(100), 0)
+            .await;
+        assert_eq!(res, 0);
+
+        let res = sleep(100)
+            .with_timeout_default(Duration::from_millis(500), 0)
+            .await;
+        assert_eq!(res, 42);
+    }
+
+    #[tokio::test]
+    async fn abort_on_drop() {
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let finished = finished.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                finished.store(true, Ordering::SeqCst);
+            }
+        }
+        .spawn_abort_on_drop();
+
+        drop(handle);
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(!finished.load(Ordering::SeqCst));
+
+        // The handle can still be awaited.
+        let handle = async { 42 }.spawn_abort_on_drop();
+        assert_eq!(handle.await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn timeout_and_cancellation() {
+        let sleep = || async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            42
+        };
+
+        let cancel_after = |duration| {
+            let token = CancellationToken::new();
+            let child = token.clone();
+
+            tokio::spawn(async move {
+                tokio::time::sleep(duration).await;
+                child.cancel();
+            });
+
+            token
+        };
+
+        // Cancellation wins.
+        let res = sleep()
+            .with_timeout(Duration::from_millis(300))
+            .with_cancellation(cancel_after(Duration::from_millis(100)))
+            .await;
+        assert_eq!(res, Err(Error::Canceled));
+
+        let res = sleep()
+            .with_cancellation(cancel_after(Duration::from_millis(100)))
+            .with_timeout(Duration::from_millis(300))
+            .await;
+        assert_eq!(res, Err(Error::Canceled));
+
+        // Timeout wins.
+        let res = sleep()
+            .with_timeout(Duration::from_millis(100))
+            .with_cancellation(cancel_after(Duration::from_millis(300)))
+            .await;
+        assert_eq!(res, Err(Error::Timeout));
+
+        let res = sleep()
+            .with_cancellation(cancel_after(Duration::from_millis(300)))
+            .with_timeout(Duration::from_millis(100))
+            .await;
+        assert_eq!(res, Err(Error::Timeout));
+
+        // Neither.
+        let res = sleep()
+            .with_timeout(Duration::from_millis(1000))
+            .with_cancellation(CancellationToken::new())
+            .await;
+        assert_eq!(res, Ok(42));
+    }
+
+    #[tokio::test]
+    async fn blocking_cancellable() {
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let blocking = || {
+            let finished = finished.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(200));
+                finished.store(true, Ordering::SeqCst);
+                42
+            }
+        };
+
+        // Completed.
+        let token = CancellationToken::new();
+        let res = spawn_blocking_cancellable(blocking(), token).await;
+        assert_eq!(res, Ok(42));
+        assert!(finished.swap(false, Ordering::SeqCst));
+
+        // Canceled before the closure finishes.
+        let token = CancellationToken::new();
+        let res = spawn_blocking_cancellable(blocking(), token.clone());
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            token.cancel();
+        });
+
+        let started = Instant::now();
+        assert_eq!(res.await, Err(Error::Canceled));
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        // The closure still runs to completion.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn join_all_cancellable() {
+        let completed = Arc::new(AtomicU32::default());
+        let task = |millis, res: Result<u32, &'static str>| {
+            let completed = completed.clone();
+
+            async move {
+                tokio::time::sleep(Duration::from_millis(millis)).await;
+                completed.fetch_add(1, Ordering::SeqCst);
+                res
+            }
+        };
+
+        // Completed.
+        let token = CancellationToken::new();
+        let res = super::join_all_cancellable(
+            [task(100, Ok(1)), task(50, Ok(2)), task(0, Ok(3))],
+            token.clone(),
+        )
+        .await;
+
+        assert_eq!(res, Ok(Ok(vec![1, 2, 3])));
+        assert_eq!(completed.swap(0, Ordering::SeqCst), 3);
+        assert!(!token.is_cancelled());
+
+        // Failed fast, canceling the rest.
+        let token = CancellationToken::new();
+        let started = Instant::now();
+        let res = super::join_all_cancellable(
+            [task(500, Ok(1)), task(50, Err("failed")), task(500, Ok(3))],
+            token.clone(),
+        )
+        .await;
+
+        assert_eq!(res, Ok(Err("failed")));
+        assert!(token.is_cancelled());
+        assert!(started.elapsed() < Duration::from_millis(300));
+
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(completed.swap(0, Ordering::SeqCst), 1);
+
+        // Canceled elsewhere.
+        let token = CancellationToken::new();
+        let res = super::join_all_cancellable([task(500, Ok(1))], token.clone());
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            token.cancel();
+        });
+
+        assert_eq!(res.await, Err(Error::Canceled));
+        assert_eq!(completed.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn race() {
+        let dropped = Arc::new(AtomicBool::new(false));
+
+        let fast = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            42
+        };
+
+        let slow = {
+            let guard = SetOnDrop(dropped.clone());
+
+            async move {
+                let _guard = guard;
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                "slow"
+            }
+        };
+
+        let output = race2(slow, fast)
+            .with_timeout(Duration::from_millis(200))
+            .await;
+
+        assert_eq!(output, Ok(Race2Output::Second(42)));
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn guarded() {
+        let dropped = Arc::new(AtomicBool::new(false));
+
+        let handle = async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            42
+        }
+        .guarded(SetOnDrop(dropped.clone()))
+        .with_timeout(Duration::from_millis(200))
+        .spawn();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        assert_eq!(handle.await.unwrap(), Err(Error::Timeout));
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn timeout_metric() {
+        use {
+            metrics_exporter_prometheus::PrometheusBuilder,
+            wc_metrics::{self as metrics, Counter, Lazy},
+        };
+
+        static TIMEOUTS: Lazy<Counter> = metrics::new("future_timeouts");
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        // Register the counter with the local recorder.
+        metrics::backend::with_local_recorder(&recorder, || TIMEOUTS.increment(0));
+
+        let res = async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            42
+        }
+        .with_timeout_metric(Duration::from_millis(100), &TIMEOUTS)
+        .await;
+
+        assert_eq!(res, Err(Error::Timeout));
+        assert!(handle.render().contains("future_timeouts 1"));
+
+        let res = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            42
+        }
+        .with_timeout_metric(Duration::from_millis(500), &TIMEOUTS)
+        .await;
+
+        assert_eq!(res, Ok(42));
+        assert!(handle.render().contains("future_timeouts 1"));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn buffered_unordered_metered() {
+        use {
+            super::StreamExt as _,
+            futures::stream::{self, StreamExt as _},
+            metrics_exporter_prometheus::PrometheusBuilder,
+            wc_metrics::{self as metrics, FutureMetrics, Lazy},
+        };
+
+        static METRICS: Lazy<FutureMetrics> = metrics::new("buffered_unordered_metered");
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        let in_progress = Arc::new(AtomicU32::new(0));
+        let max_in_progress = Arc::new(AtomicU32::new(0));
+
+        // Register the metrics with the local recorder.
+        let stream = metrics::backend::with_local_recorder(&recorder, || {
+            stream::iter(0..10).buffered_unordered_metered(3, &METRICS, |n| {
+                let in_progress = in_progress.clone();
+                let max_in_progress = max_in_progress.clone();
+
+                async move {
+                    let current = in_progress.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_progress.fetch_max(current, Ordering::SeqCst);
+
+                    tokio::time::sleep(Duration::from_millis(10 * (n % 3 + 1))).await;
+
+                    in_progress.fetch_sub(1, Ordering::SeqCst);
+                    n
+                }
+            })
+        });
+
+        let mut results: Vec<_> = stream.collect().await;
+        results.sort_unstable();
+
+        assert_eq!(results, (0..10).collect::<Vec<_>>());
+        assert_eq!(max_in_progress.load(Ordering::SeqCst), 3);
+
+        let rendered = handle.render();
+        let finished = r#"futures_finished_count{future_name="buffered_unordered_metered"} 10"#;
+        assert!(rendered.contains(finished), "{rendered}");
+    }
 }
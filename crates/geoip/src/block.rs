@@ -1,4 +1,9 @@
-use {crate::Resolver, bitflags::bitflags, std::net::IpAddr};
+use {
+    crate::{Data, Resolver},
+    bitflags::bitflags,
+    maxminddb::geoip2::City,
+    std::net::IpAddr,
+};
 
 #[cfg(feature = "middleware")]
 pub mod middleware;
@@ -29,35 +34,81 @@ pub enum Error {
     CountryNotFound,
 }
 
+/// Prefix of the zones matching a whole continent, eg. `continent:AS`.
+const CONTINENT_PREFIX: &str = "continent:";
+
 #[derive(Debug, Clone)]
-struct Zone {
-    country: String,
-    subdivisions: Vec<String>,
+enum Zone {
+    Continent(String),
+    Country {
+        country: String,
+        subdivisions: Vec<String>,
+    },
+}
+
+impl Zone {
+    fn parse(zone: &str) -> Option<Self> {
+        if let Some(prefix) = zone.get(..CONTINENT_PREFIX.len()) {
+            if prefix.eq_ignore_ascii_case(CONTINENT_PREFIX) {
+                let continent = &zone[CONTINENT_PREFIX.len()..];
+                return Some(Self::Continent(continent.to_string()));
+            }
+        }
+
+        zone.split(':')
+            .collect::<Vec<_>>()
+            .split_first()
+            .map(|(country, subdivisions)| Self::Country {
+                country: country.to_string(),
+                subdivisions: subdivisions.iter().map(|&s| s.to_string()).collect(),
+            })
+    }
+}
+
+/// Whether the [`ZoneFilter`] zones are blocked or allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FilterMode {
+    /// Only the listed zones are blocked.
+    Blocklist,
+
+    /// Everything except the listed zones is blocked.
+    Allowlist,
 }
 
 #[derive(Debug, Clone)]
 pub struct ZoneFilter {
-    blocked_zones: Vec<Zone>,
+    zones: Vec<Zone>,
+    mode: FilterMode,
     blocking_policy: BlockingPolicy,
 }
 
 impl ZoneFilter {
+    /// Creates a new [`ZoneFilter`] blocking the specified zones.
+    ///
+    /// Zones are specified as country codes (`CU`), optionally followed by
+    /// subdivision codes (`CU:12` or `CU:12:34`), or as continent codes
+    /// prefixed with `continent:` (`continent:AS`).
     pub fn new(blocked_zones: Vec<String>, blocking_policy: BlockingPolicy) -> Self {
-        let blocked_zones = blocked_zones
+        Self::with_mode(blocked_zones, FilterMode::Blocklist, blocking_policy)
+    }
+
+    /// Creates a new [`ZoneFilter`] blocking everything except the specified
+    /// zones.
+    ///
+    /// See [`ZoneFilter::new()`] for the zone format.
+    pub fn with_allowlist(allowed_zones: Vec<String>, blocking_policy: BlockingPolicy) -> Self {
+        Self::with_mode(allowed_zones, FilterMode::Allowlist, blocking_policy)
+    }
+
+    fn with_mode(zones: Vec<String>, mode: FilterMode, blocking_policy: BlockingPolicy) -> Self {
+        let zones = zones
             .iter()
-            .filter_map(|zone| {
-                zone.split(':')
-                    .collect::<Vec<_>>()
-                    .split_first()
-                    .map(|(country, subdivisions)| Zone {
-                        country: country.to_string(),
-                        subdivisions: subdivisions.iter().map(|&s| s.to_string()).collect(),
-                    })
-            })
+            .filter_map(|zone| Zone::parse(zone))
             .collect::<Vec<_>>();
 
         Self {
-            blocked_zones,
+            zones,
+            mode,
             blocking_policy,
         }
     }
@@ -68,18 +119,82 @@ impl ZoneFilter {
     where
         R: Resolver,
     {
-        let geo_data = resolver
-            .lookup_geo_data_raw(addr)
-            .map_err(|_| Error::UnableToExtractGeoData)?;
+        self.check_then(addr, resolver, |_, _| ())
+    }
+
+    /// Same as [`ZoneFilter::check()`], but also passes the geo data along with
+    /// the check result to `f` if the lookup has succeeded.
+    fn check_then<R>(
+        &self,
+        addr: IpAddr,
+        resolver: &R,
+        f: impl FnOnce(City<'_>, &Result<(), Error>),
+    ) -> Result<(), Error>
+    where
+        R: Resolver,
+    {
+        resolver
+            .with_geo_data_raw(addr, |geo_data| {
+                let result = self.check_geo_data(&geo_data);
+                f(geo_data, &result);
+                result
+            })
+            .map_err(|_| Error::UnableToExtractGeoData)?
+    }
+
+    /// Same as [`ZoneFilter::check()`], but for the geo data resolved
+    /// beforehand, eg. by an [`AsyncResolver`](crate::AsyncResolver).
+    pub fn check_data(&self, geo_data: &Data) -> Result<(), Error> {
+        self.check_geo_data(&geo_data.as_city())
+    }
+
+    fn check_geo_data(&self, geo_data: &City<'_>) -> Result<(), Error> {
+        let is_listed = self.is_listed(geo_data)?;
+
+        let is_blocked = match self.mode {
+            FilterMode::Blocklist => is_listed,
+            FilterMode::Allowlist => !is_listed,
+        };
+
+        if is_blocked {
+            Err(Error::Blocked)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks whether the geo data matches any of the zones.
+    fn is_listed(&self, geo_data: &City<'_>) -> Result<bool, Error> {
+        let continent = geo_data
+            .continent
+            .as_ref()
+            .and_then(|continent| continent.code);
+
+        let continent_listed = continent.map_or(false, |continent| {
+            self.zones.iter().any(|zone| match zone {
+                Zone::Continent(listed_continent) => {
+                    listed_continent.eq_ignore_ascii_case(continent)
+                }
+                _ => false,
+            })
+        });
+
+        if continent_listed {
+            return Ok(true);
+        }
 
         let country = geo_data
             .country
+            .as_ref()
             .and_then(|country| country.iso_code)
             .ok_or(Error::CountryNotFound)?;
 
-        let zone_blocked = self.blocked_zones.iter().any(|blocked_zone| {
-            if blocked_zone.country == country {
-                if blocked_zone.subdivisions.is_empty() {
+        let zone_listed = self.zones.iter().any(|zone| match zone {
+            Zone::Country {
+                country: listed_country,
+                subdivisions: listed_subdivisions,
+            } if listed_country == country => {
+                if listed_subdivisions.is_empty() {
                     true
                 } else {
                     geo_data
@@ -90,23 +205,18 @@ impl ZoneFilter {
                                 .iter()
                                 .filter_map(|sub| sub.iso_code)
                                 .any(|sub| {
-                                    blocked_zone
-                                        .subdivisions
+                                    listed_subdivisions
                                         .iter()
-                                        .any(|blocked_sub| sub.eq_ignore_ascii_case(blocked_sub))
+                                        .any(|listed_sub| sub.eq_ignore_ascii_case(listed_sub))
                                 })
                         })
                 }
-            } else {
-                false
             }
+
+            _ => false,
         });
 
-        if zone_blocked {
-            Err(Error::Blocked)
-        } else {
-            Ok(())
-        }
+        Ok(zone_listed)
     }
 
     /// Applies selected blocking policy to the [`Blacklist::check()`] result,
@@ -8,12 +8,18 @@
 
 use {
     super::{BlockingPolicy, Error, ZoneFilter},
-    crate::Resolver,
+    crate::{AsyncResolver, Data, Resolver},
+    axum::{
+        extract::ConnectInfo,
+        http::{Extensions, HeaderMap},
+    },
     axum_client_ip::InsecureClientIp,
-    futures::future::{self, Either, Ready},
+    futures::future::{self, BoxFuture, Either, Ready},
     http_body::Body,
     hyper::{Request, Response, StatusCode},
+    ipnet::IpNet,
     std::{
+        net::{IpAddr, SocketAddr},
         sync::Arc,
         task::{Context, Poll},
     },
@@ -30,15 +36,76 @@ struct Inner<R> {
     ip_resolver: R,
 }
 
+/// Builds the response returned for the requests rejected by the GeoBlock
+/// middleware.
+///
+/// The geo data is passed if it was resolved before the request was rejected,
+/// eg. to include the blocked country in the response.
+///
+/// Implemented for any `Fn(&Error, Option<&Data>) -> Response<B>` closure.
+pub trait BlockResponse<B> {
+    fn block_response(&self, err: &Error, geo_data: Option<&Data>) -> Response<B>;
+}
+
+impl<B, F> BlockResponse<B> for F
+where
+    F: Fn(&Error, Option<&Data>) -> Response<B>,
+{
+    fn block_response(&self, err: &Error, geo_data: Option<&Data>) -> Response<B> {
+        self(err, geo_data)
+    }
+}
+
+/// [`BlockResponse`] returning an empty body with the configured status code
+/// (`401 Unauthorized` by default) for the blocked requests and
+/// `500 Internal Server Error` otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultBlockResponse {
+    blocked_status: StatusCode,
+}
+
+impl DefaultBlockResponse {
+    pub fn new(blocked_status: StatusCode) -> Self {
+        Self { blocked_status }
+    }
+}
+
+impl Default for DefaultBlockResponse {
+    fn default() -> Self {
+        Self::new(StatusCode::UNAUTHORIZED)
+    }
+}
+
+impl<B> BlockResponse<B> for DefaultBlockResponse
+where
+    B: Default,
+{
+    fn block_response(&self, err: &Error, _geo_data: Option<&Data>) -> Response<B> {
+        let code = match err {
+            Error::Blocked => self.blocked_status,
+            Error::UnableToExtractIPAddress
+            | Error::UnableToExtractGeoData
+            | Error::CountryNotFound => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let mut response = Response::new(B::default());
+        *response.status_mut() = code;
+        response
+    }
+}
+
 /// Layer that applies the GeoBlock middleware which blocks requests base on IP
 /// geo-location.
 #[derive(Debug, Clone)]
 #[must_use]
-pub struct GeoBlockLayer<R>
+pub struct GeoBlockLayer<R, F = DefaultBlockResponse>
 where
     R: Resolver,
 {
     inner: Arc<Inner<R>>,
+    trusted_proxies: Option<Arc<[IpNet]>>,
+    block_response: F,
+    inject_geo_data: bool,
 }
 
 impl<R> GeoBlockLayer<R>
@@ -55,20 +122,94 @@ where
                 filter: ZoneFilter::new(blocked_countries, blocking_policy),
                 ip_resolver,
             }),
+            trusted_proxies: None,
+            block_response: DefaultBlockResponse::default(),
+            inject_geo_data: false,
         }
     }
+
+    /// Creates a layer blocking requests from everywhere except the specified
+    /// zones.
+    pub fn with_allowlist(
+        ip_resolver: R,
+        allowed_zones: Vec<String>,
+        blocking_policy: BlockingPolicy,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                filter: ZoneFilter::with_allowlist(allowed_zones, blocking_policy),
+                ip_resolver,
+            }),
+            trusted_proxies: None,
+            block_response: DefaultBlockResponse::default(),
+            inject_geo_data: false,
+        }
+    }
+
+    /// Sets the status code returned for the blocked requests. Defaults to
+    /// `401 Unauthorized`.
+    pub fn with_blocked_status(mut self, status: StatusCode) -> Self {
+        self.block_response = DefaultBlockResponse::new(status);
+        self
+    }
+}
+
+impl<R, F> GeoBlockLayer<R, F>
+where
+    R: Resolver,
+{
+    /// Replaces the response returned for the rejected requests, eg. to
+    /// provide a JSON body explaining why the request was blocked.
+    pub fn with_block_response<G>(self, block_response: G) -> GeoBlockLayer<R, G> {
+        GeoBlockLayer {
+            inner: self.inner,
+            trusted_proxies: self.trusted_proxies,
+            block_response,
+            inject_geo_data: self.inject_geo_data,
+        }
+    }
+
+    /// Enables the secure client IP extraction mode.
+    ///
+    /// The `X-Forwarded-For` header is only honored if the request comes from
+    /// one of the trusted proxies, and the rightmost address not belonging to
+    /// a trusted proxy is used as the client IP. Otherwise the peer address
+    /// from [`ConnectInfo`] is used.
+    ///
+    /// Only the entries up to the selected address are parsed, so malformed
+    /// entries added by the client on the left of the chain are ignored. If all
+    /// of the entries belong to trusted proxies, the leftmost one is used.
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpNet>) -> Self {
+        self.trusted_proxies = Some(trusted_proxies.into());
+        self
+    }
+
+    /// Inserts the resolved [`Data`] into the request extensions of the
+    /// allowed requests, so the handlers can extract it (eg. using axum's
+    /// `Extension<Data>`) without looking it up again.
+    ///
+    /// The data is missing if the lookup has failed, but the request was
+    /// allowed by the [`BlockingPolicy`].
+    pub fn with_geo_data_extension(mut self) -> Self {
+        self.inject_geo_data = true;
+        self
+    }
 }
 
-impl<S, R> Layer<S> for GeoBlockLayer<R>
+impl<S, R, F> Layer<S> for GeoBlockLayer<R, F>
 where
     R: Resolver,
+    F: Clone,
 {
-    type Service = GeoBlockService<S, R>;
+    type Service = GeoBlockService<S, R, F>;
 
     fn layer(&self, service: S) -> Self::Service {
         GeoBlockService {
             service,
             inner: self.inner.clone(),
+            trusted_proxies: self.trusted_proxies.clone(),
+            block_response: self.block_response.clone(),
+            inject_geo_data: self.inject_geo_data,
         }
     }
 }
@@ -77,12 +218,15 @@ where
 /// geo-location.
 #[derive(Debug, Clone)]
 #[must_use]
-pub struct GeoBlockService<S, R>
+pub struct GeoBlockService<S, R, F = DefaultBlockResponse>
 where
     R: Resolver,
 {
     service: S,
     inner: Arc<Inner<R>>,
+    trusted_proxies: Option<Arc<[IpNet]>>,
+    block_response: F,
+    inject_geo_data: bool,
 }
 
 impl<S, R> GeoBlockService<S, R>
@@ -101,15 +245,77 @@ where
                 filter: ZoneFilter::new(blocked_zones, blocking_policy),
                 ip_resolver,
             }),
+            trusted_proxies: None,
+            block_response: DefaultBlockResponse::default(),
+            inject_geo_data: false,
+        }
+    }
+
+    /// Creates a service blocking requests from everywhere except the
+    /// specified zones.
+    pub fn with_allowlist(
+        service: S,
+        ip_resolver: R,
+        allowed_zones: Vec<String>,
+        blocking_policy: BlockingPolicy,
+    ) -> Self {
+        Self {
+            service,
+            inner: Arc::new(Inner {
+                filter: ZoneFilter::with_allowlist(allowed_zones, blocking_policy),
+                ip_resolver,
+            }),
+            trusted_proxies: None,
+            block_response: DefaultBlockResponse::default(),
+            inject_geo_data: false,
         }
     }
+
+    /// Sets the status code returned for the blocked requests. Defaults to
+    /// `401 Unauthorized`.
+    pub fn with_blocked_status(mut self, status: StatusCode) -> Self {
+        self.block_response = DefaultBlockResponse::new(status);
+        self
+    }
 }
 
-impl<S, R, ReqBody, ResBody> Service<Request<ReqBody>> for GeoBlockService<S, R>
+impl<S, R, F> GeoBlockService<S, R, F>
+where
+    R: Resolver,
+{
+    /// Replaces the response returned for the rejected requests, eg. to
+    /// provide a JSON body explaining why the request was blocked.
+    pub fn with_block_response<G>(self, block_response: G) -> GeoBlockService<S, R, G> {
+        GeoBlockService {
+            service: self.service,
+            inner: self.inner,
+            trusted_proxies: self.trusted_proxies,
+            block_response,
+            inject_geo_data: self.inject_geo_data,
+        }
+    }
+
+    /// Enables the secure client IP extraction mode. See
+    /// [`GeoBlockLayer::with_trusted_proxies`].
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpNet>) -> Self {
+        self.trusted_proxies = Some(trusted_proxies.into());
+        self
+    }
+
+    /// Inserts the resolved [`Data`] into the request extensions. See
+    /// [`GeoBlockLayer::with_geo_data_extension`].
+    pub fn with_geo_data_extension(mut self) -> Self {
+        self.inject_geo_data = true;
+        self
+    }
+}
+
+impl<S, R, F, ReqBody, ResBody> Service<Request<ReqBody>> for GeoBlockService<S, R, F>
 where
     S: Service<Request<ReqBody>, Response = Response<ResBody>>,
     R: Resolver,
-    ResBody: Body + Default,
+    F: BlockResponse<ResBody>,
+    ResBody: Body,
 {
     type Error = S::Error;
     type Future = Either<S::Future, Ready<Result<Response<ResBody>, S::Error>>>;
@@ -119,33 +325,289 @@ where
         self.service.poll_ready(cx)
     }
 
-    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
         let inner = self.inner.as_ref();
+        let inject_geo_data = self.inject_geo_data;
+        let mut geo_data = None;
 
-        let result = InsecureClientIp::from(request.headers(), request.extensions())
-            .map_err(|_| Error::UnableToExtractIPAddress)
-            .and_then(|client_ip| inner.filter.check(client_ip.0, &inner.ip_resolver));
+        let result = client_ip(
+            request.headers(),
+            request.extensions(),
+            self.trusted_proxies.as_deref(),
+        )
+        .and_then(|client_ip| {
+            inner
+                .filter
+                .check_then(client_ip, &inner.ip_resolver, |data, result| {
+                    // The geo data of the rejected requests is passed to the block response.
+                    if inject_geo_data || result.is_err() {
+                        geo_data = Some(Data::from(data));
+                    }
+                })
+        });
 
         match inner.filter.apply_policy(result) {
-            Ok(_) => Either::Left(self.service.call(request)),
+            Ok(_) => {
+                if let Some(geo_data) = geo_data.filter(|_| inject_geo_data) {
+                    request.extensions_mut().insert(geo_data);
+                }
+
+                Either::Left(self.service.call(request))
+            }
 
             Err(err) => {
-                let code = match err {
-                    Error::Blocked => StatusCode::UNAUTHORIZED,
-                    Error::UnableToExtractIPAddress
-                    | Error::UnableToExtractGeoData
-                    | Error::CountryNotFound => {
-                        tracing::warn!(?err, "failed to check geoblocking");
+                if !matches!(err, Error::Blocked) {
+                    tracing::warn!(?err, "failed to check geoblocking");
+                }
 
-                        StatusCode::INTERNAL_SERVER_ERROR
+                Either::Right(future::ok(
+                    self.block_response.block_response(&err, geo_data.as_ref()),
+                ))
+            }
+        }
+    }
+}
+
+/// Same as [`GeoBlockLayer`], but resolves the geo data using an
+/// [`AsyncResolver`], awaiting the lookup instead of blocking the executor.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct AsyncGeoBlockLayer<R, F = DefaultBlockResponse> {
+    inner: Arc<Inner<R>>,
+    trusted_proxies: Option<Arc<[IpNet]>>,
+    block_response: F,
+    inject_geo_data: bool,
+}
+
+impl<R> AsyncGeoBlockLayer<R>
+where
+    R: AsyncResolver,
+{
+    pub fn new(
+        ip_resolver: R,
+        blocked_zones: Vec<String>,
+        blocking_policy: BlockingPolicy,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                filter: ZoneFilter::new(blocked_zones, blocking_policy),
+                ip_resolver,
+            }),
+            trusted_proxies: None,
+            block_response: DefaultBlockResponse::default(),
+            inject_geo_data: false,
+        }
+    }
+
+    /// Creates a layer blocking requests from everywhere except the specified
+    /// zones.
+    pub fn with_allowlist(
+        ip_resolver: R,
+        allowed_zones: Vec<String>,
+        blocking_policy: BlockingPolicy,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                filter: ZoneFilter::with_allowlist(allowed_zones, blocking_policy),
+                ip_resolver,
+            }),
+            trusted_proxies: None,
+            block_response: DefaultBlockResponse::default(),
+            inject_geo_data: false,
+        }
+    }
+
+    /// Sets the status code returned for the blocked requests. Defaults to
+    /// `401 Unauthorized`.
+    pub fn with_blocked_status(mut self, status: StatusCode) -> Self {
+        self.block_response = DefaultBlockResponse::new(status);
+        self
+    }
+}
+
+impl<R, F> AsyncGeoBlockLayer<R, F>
+where
+    R: AsyncResolver,
+{
+    /// Replaces the response returned for the rejected requests. See
+    /// [`GeoBlockLayer::with_block_response`].
+    pub fn with_block_response<G>(self, block_response: G) -> AsyncGeoBlockLayer<R, G> {
+        AsyncGeoBlockLayer {
+            inner: self.inner,
+            trusted_proxies: self.trusted_proxies,
+            block_response,
+            inject_geo_data: self.inject_geo_data,
+        }
+    }
+
+    /// Enables the secure client IP extraction mode. See
+    /// [`GeoBlockLayer::with_trusted_proxies`].
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpNet>) -> Self {
+        self.trusted_proxies = Some(trusted_proxies.into());
+        self
+    }
+
+    /// Inserts the resolved [`Data`] into the request extensions. See
+    /// [`GeoBlockLayer::with_geo_data_extension`].
+    pub fn with_geo_data_extension(mut self) -> Self {
+        self.inject_geo_data = true;
+        self
+    }
+}
+
+impl<S, R, F> Layer<S> for AsyncGeoBlockLayer<R, F>
+where
+    R: AsyncResolver,
+    F: Clone,
+{
+    type Service = AsyncGeoBlockService<S, R, F>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        AsyncGeoBlockService {
+            service,
+            inner: self.inner.clone(),
+            trusted_proxies: self.trusted_proxies.clone(),
+            block_response: self.block_response.clone(),
+            inject_geo_data: self.inject_geo_data,
+        }
+    }
+}
+
+/// Service applying the GeoBlock middleware with an [`AsyncResolver`]. Built
+/// by [`AsyncGeoBlockLayer`].
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct AsyncGeoBlockService<S, R, F = DefaultBlockResponse> {
+    service: S,
+    inner: Arc<Inner<R>>,
+    trusted_proxies: Option<Arc<[IpNet]>>,
+    block_response: F,
+    inject_geo_data: bool,
+}
+
+impl<S, R, F, ReqBody, ResBody> Service<Request<ReqBody>> for AsyncGeoBlockService<S, R, F>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    R: AsyncResolver,
+    F: BlockResponse<ResBody> + Clone + Send + 'static,
+    ReqBody: Send + 'static,
+    ResBody: Body + 'static,
+{
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+    type Response = S::Response;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        // Keep the service which was driven to readiness, leaving the clone in its
+        // place.
+        let clone = self.service.clone();
+        let mut service = std::mem::replace(&mut self.service, clone);
+
+        let inner = self.inner.clone();
+        let block_response = self.block_response.clone();
+        let inject_geo_data = self.inject_geo_data;
+        let client_ip = client_ip(
+            request.headers(),
+            request.extensions(),
+            self.trusted_proxies.as_deref(),
+        );
+
+        Box::pin(async move {
+            let mut resolved_geo_data = None;
+
+            let result = match client_ip {
+                Ok(client_ip) => match inner.ip_resolver.lookup_geo_data(client_ip).await {
+                    Ok(geo_data) => {
+                        let result = inner.filter.check_data(&geo_data);
+                        resolved_geo_data = Some(geo_data);
+                        result
+                    }
+                    Err(err) => {
+                        tracing::debug!(?err, "failed to lookup geo data");
+                        Err(Error::UnableToExtractGeoData)
+                    }
+                },
+
+                Err(err) => Err(err),
+            };
+
+            match inner.filter.apply_policy(result) {
+                Ok(_) => {
+                    if let Some(geo_data) = resolved_geo_data.filter(|_| inject_geo_data) {
+                        request.extensions_mut().insert(geo_data);
                     }
-                };
 
-                let mut response = Response::new(ResBody::default());
-                *response.status_mut() = code;
+                    service.call(request).await
+                }
 
-                Either::Right(future::ok(response))
+                Err(err) => {
+                    if !matches!(err, Error::Blocked) {
+                        tracing::warn!(?err, "failed to check geoblocking");
+                    }
+
+                    Ok(block_response.block_response(&err, resolved_geo_data.as_ref()))
+                }
             }
+        })
+    }
+}
+
+/// Extracts the client IP address, only trusting the `X-Forwarded-For` header
+/// if the peer is one of the `trusted_proxies` (if specified).
+fn client_ip(
+    headers: &HeaderMap,
+    extensions: &Extensions,
+    trusted_proxies: Option<&[IpNet]>,
+) -> Result<IpAddr, Error> {
+    let Some(trusted_proxies) = trusted_proxies else {
+        return InsecureClientIp::from(headers, extensions)
+            .map(|client_ip| client_ip.0)
+            .map_err(|_| Error::UnableToExtractIPAddress);
+    };
+
+    let is_trusted = |addr: &IpAddr| trusted_proxies.iter().any(|net| net.contains(addr));
+
+    let peer = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .ok_or(Error::UnableToExtractIPAddress)?;
+
+    if !is_trusted(&peer) {
+        return Ok(peer);
+    }
+
+    // Each proxy appends the address it received the request from, so walk the
+    // chain backwards until the first address not belonging to a trusted proxy.
+    // Anything to the left of it is controlled by the client, so it's not even
+    // parsed.
+    let forwarded: Vec<_> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    let mut leftmost = None;
+
+    for entry in forwarded.into_iter().rev() {
+        let addr = entry
+            .trim()
+            .parse::<IpAddr>()
+            .map_err(|_| Error::UnableToExtractIPAddress)?;
+
+        if !is_trusted(&addr) {
+            return Ok(addr);
         }
+
+        leftmost = Some(addr);
     }
+
+    // All of the forwarded addresses belong to trusted proxies. The leftmost one
+    // is the closest to the client.
+    Ok(leftmost.unwrap_or(peer))
 }
@@ -1,12 +1,24 @@
 use {
     crate::{
-        block::{middleware::GeoBlockLayer, BlockingPolicy},
+        block::{
+            middleware::{AsyncGeoBlockLayer, GeoBlockLayer, GeoBlockService},
+            BlockingPolicy,
+            Error,
+        },
+        AsyncResolver,
+        Data,
         LocalResolver,
     },
-    axum::body::Body,
-    hyper::{Request, Response, StatusCode},
+    axum::{body::Body, extract::ConnectInfo},
+    futures::future::BoxFuture,
+    hyper::{header, Request, Response, StatusCode},
     maxminddb::{geoip2, geoip2::City},
-    std::{convert::Infallible, net::IpAddr, sync::Arc},
+    std::{
+        convert::Infallible,
+        net::{IpAddr, SocketAddr},
+        sync::Arc,
+        time::Duration,
+    },
     tower::{Service, ServiceBuilder, ServiceExt},
 };
 
@@ -63,6 +75,24 @@ fn resolve_ip(_addr: IpAddr) -> City<'static> {
     }
 }
 
+fn resolve_ip_with_continent(addr: IpAddr) -> City<'static> {
+    City {
+        continent: Some(geoip2::city::Continent {
+            code: Some("NA"),
+            geoname_id: None,
+            names: None,
+        }),
+        ..resolve_ip(addr)
+    }
+}
+
+fn resolve_ip_no_country(addr: IpAddr) -> City<'static> {
+    City {
+        country: None,
+        ..resolve_ip(addr)
+    }
+}
+
 /// Test that a blocking list with no subdivisions blocks the country if
 /// a match is found.
 #[tokio::test]
@@ -249,3 +279,423 @@ async fn test_arc() {
 
     assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
+
+/// Test that a blocking list with a continent blocks if the continent matches.
+#[tokio::test]
+async fn test_continent_blocked() {
+    let resolver = LocalResolver::new(Some(resolve_ip_with_continent), None);
+    let blocked_zones = vec!["continent:NA".into()];
+
+    let geoblock = GeoBlockLayer::new(resolver, blocked_zones, BlockingPolicy::Block);
+
+    let mut service = ServiceBuilder::new().layer(geoblock).service_fn(handle);
+
+    let request = Request::builder()
+        .header("X-Forwarded-For", "127.0.0.1")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = service.ready().await.unwrap().call(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test that a mixed blocking list of continents, countries and subdivisions
+/// only blocks if any of the zones match.
+#[tokio::test]
+async fn test_mixed_zones() {
+    let cases: [(&[&str], StatusCode); 4] = [
+        (&["continent:EU", "IR", "CU:56"], StatusCode::OK),
+        (&["continent:eu", "IR", "CU:12"], StatusCode::UNAUTHORIZED),
+        (&["Continent:na", "IR"], StatusCode::UNAUTHORIZED),
+        (&["continent:EU", "CU"], StatusCode::UNAUTHORIZED),
+    ];
+
+    for (blocked_zones, status) in cases {
+        let resolver = LocalResolver::new(Some(resolve_ip_with_continent), None);
+        let blocked_zones = blocked_zones.iter().map(|&zone| zone.into()).collect();
+
+        let geoblock = GeoBlockLayer::new(resolver, blocked_zones, BlockingPolicy::Block);
+
+        let mut service = ServiceBuilder::new().layer(geoblock).service_fn(handle);
+
+        let request = Request::builder()
+            .header("X-Forwarded-For", "127.0.0.1")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+
+        assert_eq!(response.status(), status);
+    }
+}
+
+/// Test that an allowlist only allows the listed zones.
+#[tokio::test]
+async fn test_allowlist() {
+    let cases: [(&[&str], StatusCode); 4] = [
+        (&["CU"], StatusCode::OK),
+        (&["IR", "CU:12"], StatusCode::OK),
+        (&["CU:56"], StatusCode::UNAUTHORIZED),
+        (&["IR", "KP"], StatusCode::UNAUTHORIZED),
+    ];
+
+    for (allowed_zones, status) in cases {
+        let resolver = LocalResolver::new(Some(resolve_ip), None);
+        let allowed_zones = allowed_zones.iter().map(|&zone| zone.into()).collect();
+
+        let geoblock =
+            GeoBlockLayer::with_allowlist(resolver, allowed_zones, BlockingPolicy::Block);
+
+        let mut service = ServiceBuilder::new().layer(geoblock).service_fn(handle);
+
+        let request = Request::builder()
+            .header("X-Forwarded-For", "127.0.0.1")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+
+        assert_eq!(response.status(), status);
+    }
+}
+
+/// Test that the blocking policy handles lookup failures the same way in
+/// allowlist mode.
+#[tokio::test]
+async fn test_allowlist_policy() {
+    let cases = [
+        (BlockingPolicy::Block, StatusCode::INTERNAL_SERVER_ERROR),
+        (BlockingPolicy::AllowMissingGeoData, StatusCode::OK),
+    ];
+
+    for (blocking_policy, status) in cases {
+        let resolver = LocalResolver::new(Some(resolve_ip_no_country), None);
+
+        let mut service = GeoBlockService::with_allowlist(
+            tower::service_fn(handle),
+            resolver,
+            vec!["CU".into()],
+            blocking_policy,
+        );
+
+        let request = Request::builder()
+            .header("X-Forwarded-For", "127.0.0.1")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+
+        assert_eq!(response.status(), status);
+    }
+}
+
+/// Test that a custom block response replaces the default empty one.
+#[tokio::test]
+async fn test_block_response() {
+    let resolver = LocalResolver::new(Some(resolve_ip), None);
+    let blocked_countries = vec!["CU".into()];
+
+    let geoblock = GeoBlockLayer::new(resolver, blocked_countries, BlockingPolicy::Block)
+        .with_block_response(|err: &Error, geo_data: Option<&Data>| {
+            let body = match (err, geo_data.and_then(|data| data.country.as_deref())) {
+                (Error::Blocked, Some(country)) => {
+                    format!(r#"{{"error":"geo_blocked","country":"{country}"}}"#)
+                }
+                (Error::Blocked, None) => r#"{"error":"geo_blocked"}"#.to_owned(),
+                _ => r#"{"error":"internal"}"#.to_owned(),
+            };
+
+            Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        });
+
+    let mut service = ServiceBuilder::new().layer(geoblock).service_fn(handle);
+
+    let request = Request::builder()
+        .header("X-Forwarded-For", "127.0.0.1")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = service.ready().await.unwrap().call(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    assert_eq!(&body[..], br#"{"error":"geo_blocked","country":"CU"}"#);
+}
+
+/// Test that a configured status code is returned for the blocked requests.
+#[tokio::test]
+async fn test_blocked_status() {
+    let resolver = LocalResolver::new(Some(resolve_ip), None);
+    let blocked_countries = vec!["CU".into()];
+
+    let geoblock = GeoBlockLayer::new(resolver, blocked_countries, BlockingPolicy::Block)
+        .with_blocked_status(StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS);
+
+    let mut service = ServiceBuilder::new().layer(geoblock).service_fn(handle);
+
+    let request = Request::builder()
+        .header("X-Forwarded-For", "127.0.0.1")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = service.ready().await.unwrap().call(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS);
+
+    let resolver = LocalResolver::new(Some(resolve_ip_no_country), None);
+
+    let mut service = GeoBlockService::new(
+        tower::service_fn(handle),
+        resolver,
+        vec!["CU".into()],
+        BlockingPolicy::Block,
+    )
+    .with_blocked_status(StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS);
+
+    let request = Request::builder()
+        .header("X-Forwarded-For", "127.0.0.1")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = service.ready().await.unwrap().call(request).await.unwrap();
+
+    // Only the blocked requests are affected.
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+/// Resolves `1.1.1.1` to the blocked country, and everything else to a
+/// non-blocked one.
+fn resolve_ip_by_addr(addr: IpAddr) -> City<'static> {
+    if addr == IpAddr::from([1, 1, 1, 1]) {
+        return resolve_ip(addr);
+    }
+
+    City {
+        country: Some(geoip2::city::Country {
+            geoname_id: None,
+            is_in_european_union: None,
+            iso_code: Some("US"),
+            names: None,
+        }),
+        subdivisions: None,
+        ..resolve_ip(addr)
+    }
+}
+
+/// Test that the forwarding headers are only honored if the peer is a trusted
+/// proxy.
+#[tokio::test]
+async fn test_trusted_proxies() {
+    let cases = [
+        // Trusted peer, the forwarded client IP is used.
+        ([10, 0, 0, 1], "1.1.1.1", StatusCode::UNAUTHORIZED),
+        // Untrusted peer, the header is ignored.
+        ([2, 2, 2, 2], "1.1.1.1", StatusCode::OK),
+        // Blocked peer with a spoofed header.
+        ([1, 1, 1, 1], "2.2.2.2", StatusCode::UNAUTHORIZED),
+        // Spoofed address on the left of the chain is ignored.
+        ([10, 0, 0, 1], "1.1.1.1, 3.3.3.3, 10.0.0.2", StatusCode::OK),
+        // Client IP behind multiple trusted proxies.
+        (
+            [10, 0, 0, 1],
+            "3.3.3.3, 1.1.1.1, 10.0.0.2",
+            StatusCode::UNAUTHORIZED,
+        ),
+        // Malformed spoofed address on the left of the chain is ignored.
+        ([10, 0, 0, 1], "garbage, 1.1.1.1", StatusCode::UNAUTHORIZED),
+        // Malformed address in place of the client IP.
+        (
+            [10, 0, 0, 1],
+            "1.1.1.1, garbage",
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+        // Only trusted proxies in the chain, the leftmost one is used.
+        ([10, 0, 0, 1], "10.0.0.2, 10.0.0.3", StatusCode::OK),
+    ];
+
+    for (peer, forwarded_for, status) in cases {
+        let resolver = LocalResolver::new(Some(resolve_ip_by_addr), None);
+        let blocked_countries = vec!["CU".into()];
+
+        let geoblock = GeoBlockLayer::new(resolver, blocked_countries, BlockingPolicy::Block)
+            .with_trusted_proxies(vec!["10.0.0.0/8".parse().unwrap()]);
+
+        let mut service = ServiceBuilder::new().layer(geoblock).service_fn(handle);
+
+        let mut request = Request::builder()
+            .header("X-Forwarded-For", forwarded_for)
+            .body(Body::empty())
+            .unwrap();
+
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from((peer, 443))));
+
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+
+        assert_eq!(response.status(), status, "{peer:?} {forwarded_for}");
+    }
+}
+
+/// Responds with the country of the [`Data`] request extension, if any.
+async fn handle_geo_data(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let country = request
+        .extensions()
+        .get::<Data>()
+        .and_then(|data| data.country.as_deref().map(str::to_owned));
+
+    Ok(Response::new(Body::from(country.unwrap_or_default())))
+}
+
+/// Test that the resolved geo data is passed to the inner handler if the
+/// extension is enabled.
+#[tokio::test]
+async fn test_geo_data_extension() {
+    let cases = [(true, "CU"), (false, "")];
+
+    for (inject_geo_data, country) in cases {
+        let resolver = LocalResolver::new(Some(resolve_ip), None);
+        let blocked_countries = vec!["IR".into()];
+
+        let mut geoblock = GeoBlockLayer::new(resolver, blocked_countries, BlockingPolicy::Block);
+
+        if inject_geo_data {
+            geoblock = geoblock.with_geo_data_extension();
+        }
+
+        let mut service = ServiceBuilder::new()
+            .layer(geoblock)
+            .service_fn(handle_geo_data);
+
+        let request = Request::builder()
+            .header("X-Forwarded-For", "127.0.0.1")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, country);
+    }
+}
+
+/// Resolver resolving the loopback addresses into `CU` after a delay.
+struct DelayedResolver;
+
+impl AsyncResolver for DelayedResolver {
+    type Error = &'static str;
+
+    fn lookup_geo_data(&self, addr: IpAddr) -> BoxFuture<'_, Result<Data, Self::Error>> {
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+
+            if !addr.is_loopback() {
+                return Err("not found");
+            }
+
+            Ok(Data {
+                continent: None,
+                country: Some("CU".into()),
+                region: None,
+                city: None,
+            })
+        })
+    }
+}
+
+/// Test that the async resolver is awaited before the request is checked.
+#[tokio::test]
+async fn test_async_resolver() {
+    let cases: [(&[&str], &str, BlockingPolicy, StatusCode); 4] = [
+        (
+            &["CU"],
+            "127.0.0.1",
+            BlockingPolicy::Block,
+            StatusCode::UNAUTHORIZED,
+        ),
+        (&["IR"], "127.0.0.1", BlockingPolicy::Block, StatusCode::OK),
+        (
+            &["IR"],
+            "10.0.0.1",
+            BlockingPolicy::Block,
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+        (
+            &["IR"],
+            "10.0.0.1",
+            BlockingPolicy::AllowExtractFailure,
+            StatusCode::OK,
+        ),
+    ];
+
+    for (blocked_zones, forwarded_for, blocking_policy, status) in cases {
+        let zones = blocked_zones.iter().map(|&zone| zone.into()).collect();
+        let geoblock = AsyncGeoBlockLayer::new(DelayedResolver, zones, blocking_policy);
+
+        let mut service = ServiceBuilder::new().layer(geoblock).service_fn(handle);
+
+        let request = Request::builder()
+            .header("X-Forwarded-For", forwarded_for)
+            .body(Body::empty())
+            .unwrap();
+
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+
+        assert_eq!(
+            response.status(),
+            status,
+            "{blocked_zones:?} {forwarded_for}"
+        );
+    }
+}
+
+/// Test that the data resolved by the async resolver is inserted into the
+/// request extensions only if enabled.
+#[tokio::test]
+async fn test_async_geo_data_extension() {
+    let cases = [(true, "CU"), (false, "")];
+
+    for (inject_geo_data, country) in cases {
+        let blocked_countries = vec!["IR".into()];
+
+        let mut geoblock =
+            AsyncGeoBlockLayer::new(DelayedResolver, blocked_countries, BlockingPolicy::Block);
+
+        if inject_geo_data {
+            geoblock = geoblock.with_geo_data_extension();
+        }
+
+        let mut service = ServiceBuilder::new()
+            .layer(geoblock)
+            .service_fn(handle_geo_data);
+
+        let request = Request::builder()
+            .header("X-Forwarded-For", "127.0.0.1")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, country);
+    }
+}
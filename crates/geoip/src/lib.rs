@@ -1,5 +1,6 @@
 pub use maxminddb;
 use {
+    arc_swap::ArcSwap,
     aws_sdk_s3::{
         error::SdkError,
         operation::get_object::GetObjectError,
@@ -7,11 +8,25 @@ use {
         Client as S3Client,
     },
     bytes::Bytes,
-    maxminddb::geoip2::City,
-    std::{net::IpAddr, ops::Deref, sync::Arc},
+    futures::future::BoxFuture,
+    ipnet::IpNet,
+    maxminddb::geoip2::{self, City},
+    moka::sync::Cache,
+    std::{
+        collections::BTreeMap,
+        fmt,
+        net::IpAddr,
+        ops::Deref,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+    },
 };
 
 pub mod block;
+#[cfg(test)]
+mod tests;
 
 #[derive(Debug, Clone)]
 pub struct Data {
@@ -21,15 +36,100 @@ pub struct Data {
     pub city: Option<Arc<str>>,
 }
 
+impl From<City<'_>> for Data {
+    fn from(lookup_data: City<'_>) -> Self {
+        Self {
+            continent: lookup_data
+                .continent
+                .and_then(|continent| continent.code.map(Into::into)),
+            country: lookup_data
+                .country
+                .and_then(|country| country.iso_code.map(Into::into)),
+            region: lookup_data.subdivisions.map(|divs| {
+                divs.into_iter()
+                    .filter_map(|div| div.iso_code)
+                    .map(Into::into)
+                    .collect()
+            }),
+            city: lookup_data
+                .city
+                .and_then(|city| city.names)
+                .and_then(|city_names| city_names.get("en").copied().map(Into::into)),
+        }
+    }
+}
+
+impl Data {
+    /// Borrows the data as raw [`City`] data, leaving the fields missing from
+    /// [`Data`] empty.
+    pub(crate) fn as_city(&self) -> City<'_> {
+        City {
+            city: self.city.as_deref().map(|city| geoip2::city::City {
+                geoname_id: None,
+                names: Some(BTreeMap::from([("en", city)])),
+            }),
+            continent: self
+                .continent
+                .as_deref()
+                .map(|continent| geoip2::city::Continent {
+                    code: Some(continent),
+                    geoname_id: None,
+                    names: None,
+                }),
+            country: self
+                .country
+                .as_deref()
+                .map(|country| geoip2::city::Country {
+                    geoname_id: None,
+                    is_in_european_union: None,
+                    iso_code: Some(country),
+                    names: None,
+                }),
+            location: None,
+            postal: None,
+            registered_country: None,
+            represented_country: None,
+            subdivisions: self.region.as_ref().map(|region| {
+                region
+                    .iter()
+                    .map(|sub| geoip2::city::Subdivision {
+                        geoname_id: None,
+                        iso_code: Some(sub.as_str()),
+                        names: None,
+                    })
+                    .collect()
+            }),
+            traits: None,
+        }
+    }
+}
+
 pub trait Resolver: Clone {
     /// The error type produced by the resolver.
     type Error;
 
-    /// Lookup the raw geo data for the given IP address.
-    fn lookup_geo_data_raw(&self, addr: IpAddr) -> Result<City<'_>, Self::Error>;
-
     /// Lookup the geo data for the given IP address.
     fn lookup_geo_data(&self, addr: IpAddr) -> Result<Data, Self::Error>;
+
+    /// Lookup the raw geo data for the given IP address and pass it to `f`.
+    ///
+    /// The raw data doesn't need to outlive the call, so it's always resolved
+    /// using the latest database (see
+    /// [`MaxMindResolver::reload_from_buffer()`]).
+    fn with_geo_data_raw<U>(
+        &self,
+        addr: IpAddr,
+        f: impl FnOnce(City<'_>) -> U,
+    ) -> Result<U, Self::Error>;
+}
+
+/// Resolver whose raw geo data can be borrowed from the resolver itself.
+///
+/// Not implemented by [`MaxMindResolver`], since its database may be replaced
+/// at any time. Use [`MaxMindResolver::snapshot()`] instead.
+pub trait RawResolver: Resolver {
+    /// Lookup the raw geo data for the given IP address.
+    fn lookup_geo_data_raw(&self, addr: IpAddr) -> Result<City<'_>, Self::Error>;
 }
 
 impl<'a, T> Resolver for &'a T
@@ -38,14 +138,28 @@ where
 {
     type Error = T::Error;
 
-    fn lookup_geo_data_raw(&self, addr: IpAddr) -> Result<City<'_>, Self::Error> {
+    fn lookup_geo_data(&self, addr: IpAddr) -> Result<Data, Self::Error> {
         let r = <&T>::deref(self);
-        r.lookup_geo_data_raw(addr)
+        r.lookup_geo_data(addr)
     }
 
-    fn lookup_geo_data(&self, addr: IpAddr) -> Result<Data, Self::Error> {
+    fn with_geo_data_raw<U>(
+        &self,
+        addr: IpAddr,
+        f: impl FnOnce(City<'_>) -> U,
+    ) -> Result<U, Self::Error> {
         let r = <&T>::deref(self);
-        r.lookup_geo_data(addr)
+        r.with_geo_data_raw(addr, f)
+    }
+}
+
+impl<'a, T> RawResolver for &'a T
+where
+    T: RawResolver,
+{
+    fn lookup_geo_data_raw(&self, addr: IpAddr) -> Result<City<'_>, Self::Error> {
+        let r = <&T>::deref(self);
+        r.lookup_geo_data_raw(addr)
     }
 }
 
@@ -55,14 +169,28 @@ where
 {
     type Error = T::Error;
 
-    fn lookup_geo_data_raw(&self, addr: IpAddr) -> Result<City<'_>, Self::Error> {
+    fn lookup_geo_data(&self, addr: IpAddr) -> Result<Data, Self::Error> {
         let r = self.deref();
-        r.lookup_geo_data_raw(addr)
+        r.lookup_geo_data(addr)
     }
 
-    fn lookup_geo_data(&self, addr: IpAddr) -> Result<Data, Self::Error> {
+    fn with_geo_data_raw<U>(
+        &self,
+        addr: IpAddr,
+        f: impl FnOnce(City<'_>) -> U,
+    ) -> Result<U, Self::Error> {
         let r = self.deref();
-        r.lookup_geo_data(addr)
+        r.with_geo_data_raw(addr, f)
+    }
+}
+
+impl<T> RawResolver for Arc<T>
+where
+    T: RawResolver,
+{
+    fn lookup_geo_data_raw(&self, addr: IpAddr) -> Result<City<'_>, Self::Error> {
+        let r = self.deref();
+        r.lookup_geo_data_raw(addr)
     }
 }
 
@@ -72,6 +200,18 @@ pub enum LocalResolverError {
     NotSupported,
 }
 
+/// Resolver performing the lookups asynchronously, eg. using a remote geo
+/// API, so they don't block the executor.
+///
+/// Used by the `AsyncGeoBlockLayer` middleware.
+pub trait AsyncResolver: Send + Sync + 'static {
+    /// The error type produced by the resolver.
+    type Error: fmt::Debug;
+
+    /// Lookup the geo data for the given IP address.
+    fn lookup_geo_data(&self, addr: IpAddr) -> BoxFuture<'_, Result<Data, Self::Error>>;
+}
+
 /// Local resolver that does not need DB files.
 #[derive(Debug, Clone)]
 pub struct LocalResolver {
@@ -94,16 +234,85 @@ impl LocalResolver {
 impl Resolver for LocalResolver {
     type Error = LocalResolverError;
 
+    fn lookup_geo_data(&self, addr: IpAddr) -> Result<Data, Self::Error> {
+        self.resolver
+            .ok_or(LocalResolverError::NotSupported)
+            .map(|resolver| resolver(addr))
+    }
+
+    fn with_geo_data_raw<U>(
+        &self,
+        addr: IpAddr,
+        f: impl FnOnce(City<'_>) -> U,
+    ) -> Result<U, Self::Error> {
+        self.lookup_geo_data_raw(addr).map(f)
+    }
+}
+
+impl RawResolver for LocalResolver {
     fn lookup_geo_data_raw(&self, addr: IpAddr) -> Result<City<'_>, Self::Error> {
         self.resolver_raw
             .ok_or(LocalResolverError::NotSupported)
             .map(|resolver| resolver(addr))
     }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StaticResolverError {
+    #[error("No geoip data found for the IP address")]
+    NotFound,
+}
+
+/// Resolver using a static list of `(network, data)` rules instead of a DB
+/// file. Useful for tests and small deployments.
+///
+/// If multiple networks contain the address, the most specific one (with the
+/// longest prefix) wins. The rules are scanned linearly, so the resolver is not
+/// meant for large lists.
+#[derive(Debug, Clone)]
+pub struct StaticResolver {
+    rules: Arc<[(IpNet, Data)]>,
+}
+
+impl StaticResolver {
+    pub fn new(mut rules: Vec<(IpNet, Data)>) -> Self {
+        // Stable sort preserves the order of the rules with the same prefix, so
+        // the first one specified wins.
+        rules.sort_by(|(a, _), (b, _)| b.prefix_len().cmp(&a.prefix_len()));
+
+        Self {
+            rules: rules.into(),
+        }
+    }
+
+    fn lookup(&self, addr: IpAddr) -> Result<&Data, StaticResolverError> {
+        self.rules
+            .iter()
+            .find(|(net, _)| net.contains(&addr))
+            .map(|(_, data)| data)
+            .ok_or(StaticResolverError::NotFound)
+    }
+}
+
+impl Resolver for StaticResolver {
+    type Error = StaticResolverError;
 
     fn lookup_geo_data(&self, addr: IpAddr) -> Result<Data, Self::Error> {
-        self.resolver
-            .ok_or(LocalResolverError::NotSupported)
-            .map(|resolver| resolver(addr))
+        self.lookup(addr).cloned()
+    }
+
+    fn with_geo_data_raw<U>(
+        &self,
+        addr: IpAddr,
+        f: impl FnOnce(City<'_>) -> U,
+    ) -> Result<U, Self::Error> {
+        self.lookup_geo_data_raw(addr).map(f)
+    }
+}
+
+impl RawResolver for StaticResolver {
+    fn lookup_geo_data_raw(&self, addr: IpAddr) -> Result<City<'_>, Self::Error> {
+        self.lookup(addr).map(Data::as_city)
     }
 }
 
@@ -119,9 +328,20 @@ pub enum MaxMindResolverError {
     MaxMindDB(#[from] maxminddb::MaxMindDBError),
 }
 
+type MaxMindReader = maxminddb::Reader<Bytes>;
+
+/// Resolver using a MaxMind GeoIP2 City database.
+///
+/// The database can be replaced at runtime using
+/// [`MaxMindResolver::reload_from_buffer()`] or
+/// [`MaxMindResolver::reload_from_aws_s3()`], which affects all of the
+/// resolver's clones.
+///
+/// The lookups always use the latest database. Raw geo data which needs to
+/// outlive the lookup can be borrowed from a [`MaxMindResolver::snapshot()`].
 #[derive(Debug, Clone)]
 pub struct MaxMindResolver {
-    reader: Arc<maxminddb::Reader<Bytes>>,
+    reader: Arc<ArcSwap<MaxMindReader>>,
 }
 
 impl MaxMindResolver {
@@ -130,52 +350,219 @@ impl MaxMindResolver {
         bucket: impl Into<String>,
         key: impl Into<String>,
     ) -> Result<Self, MaxMindResolverError> {
-        let s3_object = s3_client
-            .get_object()
-            .bucket(bucket)
-            .key(key)
-            .send()
-            .await?;
-        let geo_data = s3_object.body.collect().await?.into_bytes();
-
-        Self::from_buffer(geo_data)
+        Self::from_buffer(fetch_from_aws_s3(s3_client, bucket, key).await?)
     }
 
     pub fn from_buffer(buffer: Bytes) -> Result<Self, MaxMindResolverError> {
         let reader = maxminddb::Reader::from_source(buffer)?;
         Ok(Self {
-            reader: Arc::new(reader),
+            reader: Arc::new(ArcSwap::from_pointee(reader)),
         })
     }
+
+    /// Same as [`MaxMindResolver::from_buffer()`], but caches up to `capacity`
+    /// most recently used [`Resolver::lookup_geo_data()`] results.
+    pub fn from_buffer_with_cache(
+        buffer: Bytes,
+        capacity: u64,
+    ) -> Result<CachedResolver<Self>, MaxMindResolverError> {
+        Self::from_buffer(buffer).map(|resolver| CachedResolver::new(resolver, capacity))
+    }
+
+    /// Replaces the database with the one downloaded from S3.
+    pub async fn reload_from_aws_s3(
+        &self,
+        s3_client: &S3Client,
+        bucket: impl Into<String>,
+        key: impl Into<String>,
+    ) -> Result<(), MaxMindResolverError> {
+        self.reload_from_buffer(fetch_from_aws_s3(s3_client, bucket, key).await?)
+    }
+
+    /// Replaces the database with the one contained in the buffer.
+    ///
+    /// The previous database is kept in memory until all of its snapshots are
+    /// dropped.
+    pub fn reload_from_buffer(&self, buffer: Bytes) -> Result<(), MaxMindResolverError> {
+        let reader = maxminddb::Reader::from_source(buffer)?;
+        self.reader.store(Arc::new(reader));
+        Ok(())
+    }
+
+    /// Returns the current database, which is not affected by the subsequent
+    /// reloads.
+    pub fn snapshot(&self) -> MaxMindSnapshot {
+        MaxMindSnapshot {
+            reader: self.reader.load_full(),
+        }
+    }
+}
+
+/// Database of a [`MaxMindResolver`] at the time of the
+/// [`MaxMindResolver::snapshot()`] call.
+///
+/// Keeps the database in memory until dropped, so it shouldn't outlive the
+/// raw geo data borrowed from it.
+#[derive(Debug, Clone)]
+pub struct MaxMindSnapshot {
+    reader: Arc<MaxMindReader>,
+}
+
+async fn fetch_from_aws_s3(
+    s3_client: &S3Client,
+    bucket: impl Into<String>,
+    key: impl Into<String>,
+) -> Result<Bytes, MaxMindResolverError> {
+    let s3_object = s3_client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await?;
+
+    Ok(s3_object.body.collect().await?.into_bytes())
 }
 
 impl Resolver for MaxMindResolver {
     type Error = MaxMindResolverError;
 
+    fn lookup_geo_data(&self, addr: IpAddr) -> Result<Data, Self::Error> {
+        self.with_geo_data_raw(addr, Data::from)
+    }
+
+    fn with_geo_data_raw<U>(
+        &self,
+        addr: IpAddr,
+        f: impl FnOnce(City<'_>) -> U,
+    ) -> Result<U, Self::Error> {
+        let reader = self.reader.load();
+        reader.lookup::<City>(addr).map(f).map_err(Into::into)
+    }
+}
+
+impl Resolver for MaxMindSnapshot {
+    type Error = MaxMindResolverError;
+
+    fn lookup_geo_data(&self, addr: IpAddr) -> Result<Data, Self::Error> {
+        self.with_geo_data_raw(addr, Data::from)
+    }
+
+    fn with_geo_data_raw<U>(
+        &self,
+        addr: IpAddr,
+        f: impl FnOnce(City<'_>) -> U,
+    ) -> Result<U, Self::Error> {
+        self.lookup_geo_data_raw(addr).map(f)
+    }
+}
+
+impl RawResolver for MaxMindSnapshot {
     fn lookup_geo_data_raw(&self, addr: IpAddr) -> Result<City<'_>, Self::Error> {
         self.reader.lookup::<City>(addr).map_err(Into::into)
     }
+}
+
+/// Resolver caching the [`Resolver::lookup_geo_data()`] results of the inner
+/// resolver.
+///
+/// [`RawResolver::lookup_geo_data_raw()`] results are not cached, since they
+/// borrow from the inner resolver.
+#[derive(Debug, Clone)]
+pub struct CachedResolver<R> {
+    inner: R,
+    cache: Cache<IpAddr, Data>,
+    generation: Arc<AtomicU64>,
+}
+
+impl<R> CachedResolver<R>
+where
+    R: Resolver,
+{
+    /// Creates a new [`CachedResolver`] holding up to `capacity` lookup
+    /// results.
+    pub fn new(inner: R, capacity: u64) -> Self {
+        Self {
+            inner,
+            cache: Cache::new(capacity),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Returns the inner resolver.
+    ///
+    /// Note that the cached results are not invalidated when the inner
+    /// resolver's data changes.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Removes all cached lookup results, including the ones of the lookups
+    /// in progress.
+    pub fn invalidate_cache(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.cache.invalidate_all();
+    }
+}
+
+impl CachedResolver<MaxMindResolver> {
+    /// Same as [`MaxMindResolver::reload_from_aws_s3()`], but also invalidates
+    /// the cached results.
+    pub async fn reload_from_aws_s3(
+        &self,
+        s3_client: &S3Client,
+        bucket: impl Into<String>,
+        key: impl Into<String>,
+    ) -> Result<(), MaxMindResolverError> {
+        self.reload_from_buffer(fetch_from_aws_s3(s3_client, bucket, key).await?)
+    }
+
+    /// Same as [`MaxMindResolver::reload_from_buffer()`], but also invalidates
+    /// the cached results.
+    pub fn reload_from_buffer(&self, buffer: Bytes) -> Result<(), MaxMindResolverError> {
+        self.inner.reload_from_buffer(buffer)?;
+        self.invalidate_cache();
+        Ok(())
+    }
+}
+
+impl<R> Resolver for CachedResolver<R>
+where
+    R: Resolver,
+{
+    type Error = R::Error;
+
+    fn with_geo_data_raw<U>(
+        &self,
+        addr: IpAddr,
+        f: impl FnOnce(City<'_>) -> U,
+    ) -> Result<U, Self::Error> {
+        self.inner.with_geo_data_raw(addr, f)
+    }
 
     fn lookup_geo_data(&self, addr: IpAddr) -> Result<Data, Self::Error> {
-        let lookup_data = self.lookup_geo_data_raw(addr)?;
+        if let Some(data) = self.cache.get(&addr) {
+            return Ok(data);
+        }
 
-        Ok(Data {
-            continent: lookup_data
-                .continent
-                .and_then(|continent| continent.code.map(Into::into)),
-            country: lookup_data
-                .country
-                .and_then(|country| country.iso_code.map(Into::into)),
-            region: lookup_data.subdivisions.map(|divs| {
-                divs.into_iter()
-                    .filter_map(|div| div.iso_code)
-                    .map(Into::into)
-                    .collect()
-            }),
-            city: lookup_data
-                .city
-                .and_then(|city| city.names)
-                .and_then(|city_names| city_names.get("en").copied().map(Into::into)),
-        })
+        let generation = self.generation.load(Ordering::SeqCst);
+        let data = self.inner.lookup_geo_data(addr)?;
+        self.cache.insert(addr, data.clone());
+
+        // The cache may have been invalidated during the lookup, in which case the
+        // result may be stale and should not outlive the invalidation.
+        if self.generation.load(Ordering::SeqCst) != generation {
+            self.cache.invalidate(&addr);
+        }
+
+        Ok(data)
+    }
+}
+
+impl<R> RawResolver for CachedResolver<R>
+where
+    R: RawResolver,
+{
+    fn lookup_geo_data_raw(&self, addr: IpAddr) -> Result<City<'_>, Self::Error> {
+        self.inner.lookup_geo_data_raw(addr)
     }
 }
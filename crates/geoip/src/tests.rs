@@ -0,0 +1,290 @@
+use {
+    crate::{
+        block::{BlockingPolicy, Error, ZoneFilter},
+        CachedResolver,
+        Data,
+        LocalResolver,
+        MaxMindResolver,
+        RawResolver,
+        Resolver,
+        StaticResolver,
+        StaticResolverError,
+    },
+    bytes::Bytes,
+    maxminddb::geoip2::City,
+    std::{
+        net::IpAddr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+            OnceLock,
+        },
+    },
+};
+
+static LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+
+fn resolve(addr: IpAddr) -> Data {
+    LOOKUPS.fetch_add(1, Ordering::SeqCst);
+
+    Data {
+        continent: None,
+        country: Some(addr.to_string().into()),
+        region: None,
+        city: None,
+    }
+}
+
+#[test]
+fn cached_resolver() {
+    let resolver = CachedResolver::new(LocalResolver::new(None, Some(resolve)), 16);
+    let addrs: [IpAddr; 2] = ["127.0.0.1".parse().unwrap(), "10.0.0.1".parse().unwrap()];
+
+    for _ in 0..10 {
+        for addr in addrs {
+            let data = resolver.lookup_geo_data(addr).unwrap();
+            assert_eq!(data.country.as_deref(), Some(addr.to_string().as_str()));
+        }
+    }
+
+    // The inner resolver is only called once per address.
+    assert_eq!(LOOKUPS.load(Ordering::SeqCst), 2);
+
+    // Clones share the cache.
+    resolver.clone().lookup_geo_data(addrs[0]).unwrap();
+    assert_eq!(LOOKUPS.load(Ordering::SeqCst), 2);
+
+    // Errors are not cached.
+    let resolver = CachedResolver::new(LocalResolver::new(None, None), 16);
+    assert!(resolver.lookup_geo_data(addrs[0]).is_err());
+}
+
+/// Resolver returning the number of reloads as the country, which reloads
+/// during the first lookup.
+#[derive(Clone)]
+struct ReloadingResolver {
+    reloads: Arc<AtomicUsize>,
+    cached: Arc<OnceLock<CachedResolver<ReloadingResolver>>>,
+}
+
+impl Resolver for ReloadingResolver {
+    type Error = StaticResolverError;
+
+    fn lookup_geo_data(&self, _addr: IpAddr) -> Result<Data, Self::Error> {
+        let reloads = self.reloads.load(Ordering::SeqCst);
+        if reloads == 0 {
+            self.reloads.fetch_add(1, Ordering::SeqCst);
+            self.cached.get().unwrap().invalidate_cache();
+        }
+
+        Ok(country(&reloads.to_string()))
+    }
+
+    fn with_geo_data_raw<U>(
+        &self,
+        _addr: IpAddr,
+        _f: impl FnOnce(City<'_>) -> U,
+    ) -> Result<U, Self::Error> {
+        Err(StaticResolverError::NotFound)
+    }
+}
+
+#[test]
+fn cached_resolver_reload_during_lookup() {
+    let inner = ReloadingResolver {
+        reloads: Default::default(),
+        cached: Default::default(),
+    };
+    let resolver = CachedResolver::new(inner.clone(), 16);
+    assert!(inner.cached.set(resolver.clone()).is_ok());
+
+    let addr = "127.0.0.1".parse().unwrap();
+    assert_eq!(
+        resolver.lookup_geo_data(addr).unwrap().country.as_deref(),
+        Some("0")
+    );
+
+    // The result of the lookup which started before the reload is not cached.
+    assert_eq!(
+        resolver.lookup_geo_data(addr).unwrap().country.as_deref(),
+        Some("1")
+    );
+}
+
+/// Builds a minimal IPv4 MaxMind DB resolving every address into the specified
+/// country.
+fn mmdb(country: &str) -> Bytes {
+    fn string(buf: &mut Vec<u8>, s: &str) {
+        buf.push(0x40 | s.len() as u8);
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn map(buf: &mut Vec<u8>, len: u8) {
+        buf.push(0xe0 | len);
+    }
+
+    fn uint(buf: &mut Vec<u8>, bits: u8, value: u64) {
+        let bytes = value.to_be_bytes();
+        let bytes = &bytes[bytes.iter().take_while(|&&b| b == 0).count()..];
+        let len = bytes.len() as u8;
+
+        match bits {
+            16 => buf.push(0xa0 | len),
+            32 => buf.push(0xc0 | len),
+            // Extended type.
+            _ => buf.extend_from_slice(&[len, 9 - 7]),
+        }
+
+        buf.extend_from_slice(bytes);
+    }
+
+    // Search tree with a single node, both records of which point to the
+    // beginning of the data section.
+    let node_count = 1;
+    let mut buf = vec![0, 0, 17, 0, 0, 17];
+
+    // Data section separator.
+    buf.extend_from_slice(&[0; 16]);
+
+    map(&mut buf, 1);
+    string(&mut buf, "country");
+    map(&mut buf, 1);
+    string(&mut buf, "iso_code");
+    string(&mut buf, country);
+
+    buf.extend_from_slice(b"\xab\xcd\xefMaxMind.com");
+
+    map(&mut buf, 9);
+    string(&mut buf, "node_count");
+    uint(&mut buf, 32, node_count);
+    string(&mut buf, "record_size");
+    uint(&mut buf, 16, 24);
+    string(&mut buf, "ip_version");
+    uint(&mut buf, 16, 4);
+    string(&mut buf, "database_type");
+    string(&mut buf, "Test-City");
+    string(&mut buf, "languages");
+    // Empty array (extended type).
+    buf.extend_from_slice(&[0, 11 - 7]);
+    string(&mut buf, "binary_format_major_version");
+    uint(&mut buf, 16, 2);
+    string(&mut buf, "binary_format_minor_version");
+    uint(&mut buf, 16, 0);
+    string(&mut buf, "build_epoch");
+    uint(&mut buf, 64, 1_700_000_000);
+    string(&mut buf, "description");
+    map(&mut buf, 0);
+
+    buf.into()
+}
+
+#[test]
+fn maxmind_reload() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let country = |resolver: &MaxMindResolver| {
+        resolver
+            .with_geo_data_raw(addr, |data| {
+                data.country.and_then(|c| c.iso_code).map(str::to_owned)
+            })
+            .unwrap()
+    };
+
+    let resolver = MaxMindResolver::from_buffer(mmdb("CU")).unwrap();
+    let clone = resolver.clone();
+
+    let snapshot = resolver.snapshot();
+    assert_eq!(country(&resolver).as_deref(), Some("CU"));
+
+    resolver.reload_from_buffer(mmdb("IR")).unwrap();
+
+    // Clones share the reloaded database.
+    assert_eq!(country(&resolver).as_deref(), Some("IR"));
+    assert_eq!(country(&clone).as_deref(), Some("IR"));
+    assert_eq!(
+        clone.lookup_geo_data(addr).unwrap().country.as_deref(),
+        Some("IR")
+    );
+
+    // Raw data borrowed from a snapshot taken before the reload is still valid.
+    let raw = snapshot.lookup_geo_data_raw(addr).unwrap();
+    assert_eq!(raw.country.and_then(|c| c.iso_code), Some("CU"));
+
+    let reloaded = clone.snapshot();
+    let reloaded_raw = reloaded.lookup_geo_data_raw(addr).unwrap();
+    assert_eq!(reloaded_raw.country.and_then(|c| c.iso_code), Some("IR"));
+}
+
+#[test]
+fn cached_maxmind_reload() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let resolver = MaxMindResolver::from_buffer_with_cache(mmdb("CU"), 8).unwrap();
+
+    assert_eq!(
+        resolver.lookup_geo_data(addr).unwrap().country.as_deref(),
+        Some("CU")
+    );
+
+    resolver.reload_from_buffer(mmdb("IR")).unwrap();
+
+    // Cached results are invalidated by the reload.
+    assert_eq!(
+        resolver.lookup_geo_data(addr).unwrap().country.as_deref(),
+        Some("IR")
+    );
+    assert_eq!(
+        resolver
+            .inner()
+            .lookup_geo_data(addr)
+            .unwrap()
+            .country
+            .as_deref(),
+        Some("IR")
+    );
+}
+
+fn country(country: &str) -> Data {
+    Data {
+        continent: None,
+        country: Some(country.into()),
+        region: None,
+        city: None,
+    }
+}
+
+#[test]
+fn static_resolver() {
+    let resolver = StaticResolver::new(vec![
+        ("10.0.0.0/8".parse().unwrap(), country("CU")),
+        ("10.1.2.0/24".parse().unwrap(), country("IR")),
+        ("10.1.0.0/16".parse().unwrap(), country("KP")),
+        ("2001:db8::/32".parse().unwrap(), country("SY")),
+    ]);
+
+    let lookup = |addr: &str| {
+        resolver
+            .lookup_geo_data(addr.parse().unwrap())
+            .map(|data| data.country.unwrap().to_string())
+    };
+
+    // The longest prefix wins regardless of the order of the rules.
+    assert_eq!(lookup("10.1.2.3").unwrap(), "IR");
+    assert_eq!(lookup("10.1.3.4").unwrap(), "KP");
+    assert_eq!(lookup("10.2.3.4").unwrap(), "CU");
+    assert_eq!(lookup("2001:db8::1").unwrap(), "SY");
+
+    assert!(matches!(
+        lookup("192.168.0.1"),
+        Err(StaticResolverError::NotFound)
+    ));
+
+    // Raw data is resolved as well, so the resolver can be used for blocking.
+    let filter = ZoneFilter::new(vec!["IR".into()], BlockingPolicy::Block);
+    let check = |addr: &str| filter.check(addr.parse().unwrap(), &resolver);
+
+    assert!(matches!(check("10.1.2.3"), Err(Error::Blocked)));
+    assert!(check("10.1.3.4").is_ok());
+    assert!(matches!(
+        check("192.168.0.1"),
+        Err(Error::UnableToExtractGeoData)
+    ));
+}
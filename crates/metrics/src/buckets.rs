@@ -0,0 +1,60 @@
+/// Standard histogram bucket layouts.
+///
+/// Histogram buckets are configured by the exporter rather than by this crate,
+/// eg. using
+/// [`PrometheusBuilder::set_buckets_for_metric`](https://docs.rs/metrics-exporter-prometheus/latest/metrics_exporter_prometheus/struct.PrometheusBuilder.html#method.set_buckets_for_metric).
+/// Using these presets across services keeps the dashboards comparable.
+pub struct Buckets;
+
+impl Buckets {
+    /// Buckets for latencies recorded in milliseconds.
+    pub const fn latency_ms() -> &'static [f64] {
+        &[
+            1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0,
+        ]
+    }
+
+    /// Buckets for sizes recorded in bytes, from 64B to 64MiB.
+    pub const fn size_bytes() -> &'static [f64] {
+        &[
+            64.0, 256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0,
+            16777216.0, 67108864.0,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::Buckets,
+        crate::{Histogram, Lazy},
+        metrics_exporter_prometheus::{Matcher, PrometheusBuilder},
+    };
+
+    #[test]
+    fn latency_ms_buckets() {
+        let recorder = PrometheusBuilder::new()
+            .set_buckets_for_metric(
+                Matcher::Full("buckets_latency".into()),
+                Buckets::latency_ms(),
+            )
+            .unwrap()
+            .build_recorder();
+        let handle = recorder.handle();
+
+        static HISTOGRAM: Lazy<Histogram> = crate::new("buckets_latency");
+
+        metrics::with_local_recorder(&recorder, || HISTOGRAM.record(3.0));
+
+        let boundaries: Vec<f64> = handle
+            .render()
+            .lines()
+            .filter_map(|line| line.strip_prefix("buckets_latency_bucket{le=\""))
+            .filter_map(|line| line.split_once('"'))
+            .filter(|(le, _)| *le != "+Inf")
+            .map(|(le, _)| le.parse().unwrap())
+            .collect();
+
+        assert_eq!(boundaries, Buckets::latency_ms());
+    }
+}
@@ -0,0 +1,299 @@
+use {
+    crate::sealed::{Attrs, Execute, Increment, Metric},
+    metrics::{counter, gauge, Counter, Gauge},
+    parking_lot::{Condvar, Mutex},
+    std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+            Once,
+            Weak,
+        },
+        time::{Duration, Instant},
+    },
+};
+
+/// `alpha` of the [`EwmaRateCounter`]s registered without
+/// [`Builder::with_ewma`](crate::Builder::with_ewma).
+pub const DEFAULT_EWMA_ALPHA: f64 = 0.2;
+
+/// Sampling interval of the [`EwmaRateCounter`]s registered without
+/// [`Builder::with_ewma`](crate::Builder::with_ewma).
+pub const DEFAULT_EWMA_INTERVAL: Duration = Duration::from_secs(5);
+
+/// [`Counter`] which additionally exposes an exponentially-weighted moving
+/// average (EWMA) of its rate as a `{name}_ewma_rate` [`Gauge`].
+///
+/// The rate is sampled once per `interval`: the counter delta since the
+/// previous sample is converted into a per-second rate and blended into the
+/// average, with `alpha` being the weight of the latest sample. All of the
+/// counters are sampled by a single background thread, which stops sampling a
+/// counter after it's dropped.
+///
+/// Can be used as a [`Lazy`](crate::Lazy) static metric, see
+/// [`Builder::with_ewma`](crate::Builder::with_ewma).
+pub struct EwmaRateCounter {
+    counter: Counter,
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    total: AtomicU64,
+    gauge: Gauge,
+    alpha: f64,
+    interval: Duration,
+    state: Mutex<State>,
+}
+
+struct State {
+    sampled_at: Instant,
+    last_total: u64,
+    rate: Option<f64>,
+}
+
+impl EwmaRateCounter {
+    /// Creates a new [`EwmaRateCounter`] and registers it with the sampling
+    /// thread.
+    ///
+    /// `alpha` should be within `(0, 1]`, higher values make the average react
+    /// faster to rate changes.
+    pub fn new(name: &'static str, alpha: f64, interval: Duration) -> Self {
+        Self::from_parts(
+            counter!(name),
+            gauge!(format!("{name}_ewma_rate")),
+            alpha,
+            interval,
+        )
+    }
+
+    fn from_parts(counter: Counter, gauge: Gauge, alpha: f64, interval: Duration) -> Self {
+        assert!(
+            alpha > 0.0 && alpha <= 1.0,
+            "`alpha` should be within (0, 1]"
+        );
+
+        let inner = Arc::new(Inner {
+            total: AtomicU64::new(0),
+            gauge,
+            alpha,
+            interval,
+            state: Mutex::new(State {
+                sampled_at: Instant::now(),
+                last_total: 0,
+                rate: None,
+            }),
+        });
+
+        SAMPLER.register(&inner);
+
+        Self { counter, inner }
+    }
+
+    /// See [`Counter::increment`].
+    pub fn increment(&self, value: u64) {
+        self.counter.increment(value);
+        self.inner.total.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// Returns the current EWMA of the rate (per second).
+    pub fn rate(&self) -> f64 {
+        self.inner.state.lock().rate.unwrap_or_default()
+    }
+}
+
+impl Metric for EwmaRateCounter {
+    fn register(attrs: &Attrs) -> Self {
+        let (alpha, interval) = attrs
+            .ewma()
+            .unwrap_or((DEFAULT_EWMA_ALPHA, DEFAULT_EWMA_INTERVAL));
+
+        let gauge = gauge!(format!("{}_ewma_rate", attrs.name()), attrs.labels().iter());
+
+        Self::from_parts(Counter::register(attrs), gauge, alpha, interval)
+    }
+}
+
+impl<T> Execute<Increment<T>, ()> for EwmaRateCounter
+where
+    T: Into<u64>,
+{
+    fn execute(&self, op: Increment<T>, _labels: ()) {
+        self.increment(op.0.into())
+    }
+}
+
+impl Inner {
+    /// Samples the rate if the `interval` has passed since the previous sample,
+    /// returning the time the next sample is due.
+    fn sample_if_due(&self, now: Instant) -> Instant {
+        let mut state = self.state.lock();
+
+        let elapsed = now.saturating_duration_since(state.sampled_at);
+        if elapsed >= self.interval {
+            state.sampled_at = now;
+            self.sample(&mut state, elapsed);
+        }
+
+        state.sampled_at + self.interval
+    }
+
+    fn sample(&self, state: &mut State, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return;
+        }
+
+        let total = self.total.load(Ordering::Relaxed);
+
+        let sample = total.saturating_sub(state.last_total) as f64 / secs;
+        state.last_total = total;
+
+        let rate = match state.rate {
+            Some(rate) => self.alpha * sample + (1.0 - self.alpha) * rate,
+            None => sample,
+        };
+
+        state.rate = Some(rate);
+        self.gauge.set(rate);
+    }
+}
+
+static SAMPLER: Sampler = Sampler {
+    counters: Mutex::new(Vec::new()),
+    registered: Condvar::new(),
+    started: Once::new(),
+};
+
+/// Background thread sampling all of the [`EwmaRateCounter`]s, sleeping until
+/// the nearest sample is due.
+struct Sampler {
+    counters: Mutex<Vec<Weak<Inner>>>,
+    registered: Condvar,
+    started: Once,
+}
+
+impl Sampler {
+    fn register(&'static self, inner: &Arc<Inner>) {
+        self.started.call_once(|| {
+            std::thread::Builder::new()
+                .name("ewma_rate_sampler".into())
+                .spawn(move || self.run())
+                .expect("failed to spawn EWMA sampling thread");
+        });
+
+        self.counters.lock().push(Arc::downgrade(inner));
+
+        // The new counter may be due before the sampler would wake up.
+        self.registered.notify_one();
+    }
+
+    fn run(&self) {
+        let mut counters = self.counters.lock();
+
+        loop {
+            let now = Instant::now();
+            let mut next_due = None;
+
+            counters.retain(|inner| {
+                // The counter has been dropped.
+                let Some(inner) = inner.upgrade() else {
+                    return false;
+                };
+
+                let due = inner.sample_if_due(now);
+                next_due = Some(next_due.map_or(due, |next: Instant| next.min(due)));
+                true
+            });
+
+            match next_due {
+                Some(deadline) => {
+                    self.registered.wait_until(&mut counters, deadline);
+                }
+                None => self.registered.wait(&mut counters),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::EwmaRateCounter,
+        crate::Lazy,
+        metrics_exporter_prometheus::PrometheusBuilder,
+        std::time::Duration,
+    };
+
+    #[test]
+    fn ewma_rate_converges() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        let interval = Duration::from_secs(1);
+        let counter = metrics::with_local_recorder(&recorder, || {
+            EwmaRateCounter::new("ewma_counter", 0.5, interval)
+        });
+
+        // Sample at the simulated instants, which are ahead of the real time, to
+        // not depend on the timing of the background thread.
+        let mut now = counter.inner.state.lock().sampled_at;
+        let mut sample = || {
+            now += interval;
+            assert_eq!(counter.inner.sample_if_due(now), now + interval);
+        };
+
+        for _ in 0..5 {
+            counter.increment(100);
+            sample();
+        }
+        assert_eq!(counter.rate(), 100.0);
+
+        for _ in 0..30 {
+            for _ in 0..10 {
+                counter.increment(1);
+            }
+            sample();
+        }
+        assert!((counter.rate() - 10.0).abs() < 0.01, "{}", counter.rate());
+
+        // Not due yet.
+        counter.increment(1000);
+        assert_eq!(counter.inner.sample_if_due(now), now + interval);
+        assert!((counter.rate() - 10.0).abs() < 0.01, "{}", counter.rate());
+
+        let rendered = handle.render();
+        let gauge: f64 = rendered
+            .lines()
+            .find_map(|line| line.strip_prefix("ewma_counter_ewma_rate "))
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        assert!((gauge - 10.0).abs() < 0.01, "{gauge}");
+        assert!(rendered.contains("ewma_counter 1800"));
+    }
+
+    #[test]
+    fn lazy_ewma_rate_counter() {
+        static COUNTER: Lazy<EwmaRateCounter> = crate::builder("lazy_ewma_counter")
+            .with_static_labels(&[("kind", "test")])
+            .with_ewma(1.0, Duration::from_millis(50))
+            .build();
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || COUNTER.increment(10));
+
+        // Sampled by the background thread.
+        let started = std::time::Instant::now();
+        while COUNTER.rate() == 0.0 {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let rendered = handle.render();
+        assert!(rendered.contains(r#"lazy_ewma_counter{kind="test"} 10"#));
+        assert!(rendered.contains(r#"lazy_ewma_counter_ewma_rate{kind="test"}"#));
+    }
+}
@@ -1,8 +1,13 @@
 use wc_metrics::{
+    self as metrics,
     counter,
     enum_ordinalize::Ordinalize,
+    label_name,
     BoolLabel,
+    Counter,
     EnumLabel,
+    LabeledCounter,
+    Lazy,
     OptionalBoolLabel,
     OptionalEnumLabel,
     OptionalStringLabel,
@@ -24,10 +29,17 @@ impl wc_metrics::Enum for MyEnum {
     }
 }
 
+type MyEnumLabel = EnumLabel<{ label_name("e") }, MyEnum>;
+
+static COUNTER21: Lazy<LabeledCounter<MyEnumLabel>> = metrics::new("counter21");
+static COUNTER22: Lazy<LabeledCounter<MyEnumLabel>> = metrics::new("counter22");
+
 pub fn counters(v: u64) {
     let s = "a";
     let b = true;
     let u = 42;
+    let chain_id: u32 = 137;
+    let port: u16 = 8080;
     let e = MyEnum::A;
 
     counter!("counter1").increment(v);
@@ -101,4 +113,20 @@ pub fn counters(v: u64) {
         "st2" => "2"
     )
     .increment(v);
+
+    for _ in 0..v {
+        COUNTER21.increment_one((MyEnumLabel::new(e),));
+    }
+
+    // Can be cached and reused without resolving the labels again.
+    let counter: &'static Counter = COUNTER22.resolve_labels((MyEnumLabel::new(e),));
+    counter.increment(v);
+
+    counter!("counter23", StringLabel<"chain_id", u32> => &chain_id).increment(v);
+
+    counter!("counter24", "description24",
+        StringLabel<"port", u16> => &port,
+        "st" => "1"
+    )
+    .increment(v);
 }
@@ -28,6 +28,8 @@ pub fn gauges(v: f64) {
     let s = "a";
     let b = true;
     let u = 42;
+    let chain_id: u32 = 137;
+    let port: u16 = 8080;
     let e = MyEnum::A;
 
     gauge!("gauge1").set(v);
@@ -101,4 +103,12 @@ pub fn gauges(v: f64) {
         "st2" => "2"
     )
     .set(v);
+
+    gauge!("gauge21", StringLabel<"chain_id", u32> => &chain_id).set(v);
+
+    gauge!("gauge22", "description22",
+        StringLabel<"port", u16> => &port,
+        "st" => "1"
+    )
+    .set(v);
 }
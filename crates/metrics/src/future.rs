@@ -38,6 +38,7 @@ use {
     std::{
         future::Future,
         pin::Pin,
+        sync::atomic::{AtomicI64, Ordering},
         task::{Context, Poll},
         time::{Duration, Instant},
     },
@@ -52,9 +53,11 @@ pub mod name {
     pub const FUTURES_STARTED: &str = "futures_started_count";
     pub const FUTURES_FINISHED: &str = "futures_finished_count";
     pub const FUTURES_CANCELLED: &str = "futures_cancelled_count";
+    pub const FUTURES_IN_FLIGHT: &str = "futures_in_flight";
 
     pub const FUTURE_POLL_DURATION: &str = "future_poll_duration";
     pub const FUTURE_POLL_DURATION_MAX: &str = "future_poll_duration_max";
+    pub const FUTURE_POLL_DURATION_PER_POLL: &str = "future_poll_duration_per_poll";
     pub const FUTURE_POLLS: &str = "future_polls_count";
 }
 
@@ -67,9 +70,12 @@ pub struct Metrics {
     started: Counter,
     finished: Counter,
     cancelled: Counter,
+    in_flight: Gauge,
+    in_flight_count: AtomicI64,
 
     poll_duration: Histogram,
     poll_duration_max: Gauge,
+    poll_duration_per_poll: Histogram,
     polls: Counter,
 }
 
@@ -86,13 +92,44 @@ impl Metric for Metrics {
             started: counter!(name::FUTURES_STARTED, labels.iter()),
             finished: counter!(name::FUTURES_FINISHED, labels.iter()),
             cancelled: counter!(name::FUTURES_CANCELLED, labels.iter()),
+            in_flight: gauge!(name::FUTURES_IN_FLIGHT, labels.iter()),
+            in_flight_count: AtomicI64::new(0),
             poll_duration: histogram!(name::FUTURE_POLL_DURATION, labels.iter()),
             poll_duration_max: gauge!(name::FUTURE_POLL_DURATION_MAX, labels.iter()),
+            poll_duration_per_poll: histogram!(name::FUTURE_POLL_DURATION_PER_POLL, labels.iter()),
             polls: counter!(name::FUTURE_POLLS, labels.iter()),
         }
     }
 }
 
+impl Metrics {
+    /// Returns the number of futures which have been started, but haven't yet
+    /// finished or been cancelled.
+    ///
+    /// Same as the current value of the `futures_in_flight` gauge.
+    pub fn in_flight_count(&self) -> i64 {
+        self.in_flight_count.load(Ordering::Relaxed)
+    }
+
+    fn start(&self) {
+        self.started.increment(1);
+        self.in_flight.increment(1);
+        self.in_flight_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn stop(&self) {
+        self.in_flight.decrement(1);
+        self.in_flight_count.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Lazy<Metrics> {
+    /// See [`Metrics::in_flight_count`].
+    pub fn in_flight_count(&'static self) -> i64 {
+        self.get_or_register().in_flight_count()
+    }
+}
+
 /// Convienience extension `trait` for creating [`Metered`] [`Future`]s.
 pub trait FutureExt: Sized {
     /// Consumes the future, returning a new future that records the executiion
@@ -159,13 +196,16 @@ impl<F: Future> Future for Metered<F> {
 
         if state.started_at.is_none() {
             state.started_at = Some(Instant::now());
-            state.metrics.started.increment(1);
+            state.metrics.start();
         }
 
         let poll_started_at = Instant::now();
         let result = this.future.poll(cx);
         let poll_duration = poll_started_at.elapsed();
 
+        // Recorded individually to be able to spot the executor-blocking polls.
+        state.metrics.poll_duration_per_poll.record(poll_duration);
+
         state.poll_duration_sum += poll_duration;
         state.poll_duration_max = state.poll_duration_max.max(poll_duration);
         state.polls_count += 1;
@@ -174,6 +214,7 @@ impl<F: Future> Future for Metered<F> {
             state.is_finished = true;
 
             state.metrics.finished.increment(1);
+            state.metrics.stop();
 
             if let Some(started_at) = state.started_at {
                 state.metrics.duration.record(started_at.elapsed())
@@ -190,7 +231,8 @@ impl Drop for State {
             self.metrics.cancelled.increment(1);
 
             if let Some(started_at) = self.started_at {
-                self.metrics.cancelled_duration.record(started_at.elapsed())
+                self.metrics.cancelled_duration.record(started_at.elapsed());
+                self.metrics.stop();
             }
         }
 
@@ -205,3 +247,65 @@ impl<F: Future> FusedFuture for Metered<F> {
         self.state.is_finished
     }
 }
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{FutureExt as _, Metrics},
+        crate::Lazy,
+        futures::{channel::oneshot, future, task, FutureExt as _},
+        metrics_exporter_prometheus::PrometheusBuilder,
+    };
+
+    #[test]
+    fn in_flight_count() {
+        static METRICS: Lazy<Metrics> = crate::new("in_flight_future");
+
+        let mut cx = task::Context::from_waker(task::noop_waker_ref());
+
+        let (tx, rx) = oneshot::channel::<()>();
+        let mut finished = Box::pin(rx.with_metrics(&METRICS));
+        let mut cancelled = Box::pin(future::pending::<()>().with_metrics(&METRICS));
+        let not_started = future::pending::<()>().with_metrics(&METRICS);
+
+        assert_eq!(METRICS.in_flight_count(), 0);
+
+        assert!(finished.poll_unpin(&mut cx).is_pending());
+        assert!(cancelled.poll_unpin(&mut cx).is_pending());
+        assert_eq!(METRICS.in_flight_count(), 2);
+
+        tx.send(()).unwrap();
+        assert!(finished.poll_unpin(&mut cx).is_ready());
+        assert_eq!(METRICS.in_flight_count(), 1);
+
+        drop(cancelled);
+        drop(not_started);
+        drop(finished);
+        assert_eq!(METRICS.in_flight_count(), 0);
+    }
+
+    #[test]
+    fn poll_duration_per_poll() {
+        static METRICS: Lazy<Metrics> = crate::new("per_poll_future");
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        let mut cx = task::Context::from_waker(task::noop_waker_ref());
+
+        metrics::with_local_recorder(&recorder, || {
+            let mut fut = Box::pin(future::pending::<()>().with_metrics(&METRICS));
+            for _ in 0..3 {
+                assert!(fut.poll_unpin(&mut cx).is_pending());
+            }
+        });
+
+        let rendered = handle.render();
+        let expected = r#"future_poll_duration_per_poll_count{future_name="per_poll_future"} 3"#;
+        assert!(rendered.contains(expected), "{rendered}");
+
+        // The sum-based metric is still recorded once per future.
+        let expected = r#"future_poll_duration_count{future_name="per_poll_future"} 1"#;
+        assert!(rendered.contains(expected), "{rendered}");
+    }
+}
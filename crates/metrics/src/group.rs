@@ -0,0 +1,57 @@
+use {
+    crate::Lazy,
+    metrics::{Histogram, IntoF64},
+};
+
+/// Group of [`Histogram`]s recording the same values.
+///
+/// Useful for recording a value into multiple histograms of different
+/// resolutions, eg. a fine-grained and a coarse-grained one for the same
+/// latency.
+pub struct HistogramGroup<const N: usize> {
+    members: [&'static Lazy<Histogram>; N],
+}
+
+impl<const N: usize> HistogramGroup<N> {
+    /// Creates a new [`HistogramGroup`] of the specified histograms.
+    pub const fn new(members: [&'static Lazy<Histogram>; N]) -> Self {
+        Self { members }
+    }
+
+    /// Records the value into every histogram of the group.
+    pub fn record<T: IntoF64>(&self, value: T) {
+        let value = value.into_f64();
+
+        for histogram in self.members {
+            histogram.record(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::HistogramGroup,
+        crate::{Histogram, Lazy},
+        metrics_exporter_prometheus::PrometheusBuilder,
+    };
+
+    #[test]
+    fn records_into_every_member() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        static FINE: Lazy<Histogram> = crate::new("group_fine");
+        static COARSE: Lazy<Histogram> = crate::new("group_coarse");
+        static GROUP: HistogramGroup<2> = HistogramGroup::new([&FINE, &COARSE]);
+
+        metrics::with_local_recorder(&recorder, || GROUP.record(42));
+
+        let rendered = handle.render();
+
+        for name in ["group_fine", "group_coarse"] {
+            assert!(rendered.contains(&format!("{name}_sum 42")), "{rendered}");
+            assert!(rendered.contains(&format!("{name}_count 1")), "{rendered}");
+        }
+    }
+}
@@ -8,7 +8,13 @@ use {
     metrics::Label,
     parking_lot::Mutex,
     smallvec::SmallVec,
-    std::{borrow::Borrow, collections::HashMap, sync::Arc},
+    std::{
+        borrow::Borrow,
+        cell::RefCell,
+        collections::HashMap,
+        sync::{Arc, OnceLock},
+    },
+    thread_local::ThreadLocal,
 };
 
 pub type DynamicLabels = SmallVec<[Label; 4]>;
@@ -223,7 +229,14 @@ where
 ///
 /// Despite its name the label can accept any type that implements [`ToString`],
 /// not just [`String`]s. The most frequent use-case (aside from the actual
-/// strings) - numbers.
+/// strings) - numbers, eg. `StringLabel<NAME, u32>` for chain IDs or
+/// `StringLabel<NAME, u16>` for ports.
+///
+/// Every distinct label value creates a separate metric which is never
+/// deallocated, so high-cardinality values (eg. arbitrary user-provided IDs)
+/// will keep growing both the memory usage and the number of exported time
+/// series. The hot values are resolved from a small per-thread cache, the
+/// rest go through the shared [`HashMap`] lookup.
 ///
 /// Due to the lack of `&'static str` const generics at the moment the label
 /// name should be specified using the following hack:
@@ -260,22 +273,53 @@ impl<const NAME: LabelName, T> AsRef<T> for StringLabel<NAME, T> {
     }
 }
 
-pub struct StringCollection<T, M: 'static> {
-    inner: ArcSwap<HashMap<T, &'static M>>,
+/// Label value of the metric shared by the [`StringLabel`] values refused due
+/// to [`Builder::with_max_string_labels`](crate::Builder::with_max_string_labels).
+pub const OVERFLOW_LABEL_VALUE: &str = "__overflow__";
+
+/// Name of the counter of the [`StringLabel`] resolutions refused due to
+/// [`Builder::with_max_string_labels`](crate::Builder::with_max_string_labels).
+///
+/// Labeled by the name of the affected metric (`metric` label).
+pub const DROPPED_LABELS_METRIC: &str = "wc_metrics_dropped_labels";
+
+pub struct StringCollection<T: Send + Sync + 'static, M: Send + Sync + 'static> {
+    inner: ArcSwap<HashMap<T, &'static StringEntry<T, M>>>,
     mutex: Mutex<()>,
     attrs: Attrs,
+    overflow: OnceLock<(&'static M, metrics::Counter)>,
+
+    /// Per-thread cache of the recently resolved entries, allowing hot label
+    /// values to skip hashing and the `inner` lookup.
+    ///
+    /// Cached entries never go stale, as the entries are leaked and never
+    /// removed.
+    cache: ThreadLocal<RefCell<LabelCache<T, M>>>,
+}
+
+const LABEL_CACHE_SIZE: usize = 4;
+
+/// Most recently used entries first.
+type LabelCache<T, M> = [Option<&'static StringEntry<T, M>>; LABEL_CACHE_SIZE];
+
+/// Leaked metric of a [`StringCollection`] along with its label value.
+struct StringEntry<T, M> {
+    label: T,
+    metric: M,
 }
 
 impl<const NAME: LabelName, T, M> DynamicLabel<M> for StringLabel<NAME, T>
 where
-    M: 'static,
+    T: Send + Sync + 'static,
+    M: Send + Sync + 'static,
 {
     type MetricCollection = StringCollection<T, M>;
 }
 
 impl<const NAME: LabelName, T, M> Metric for WithLabel<StringLabel<NAME, T>, M>
 where
-    M: Metric + 'static,
+    T: Send + Sync + 'static,
+    M: Metric + Send + Sync + 'static,
 {
     fn register(attrs: &Attrs) -> Self {
         Self {
@@ -283,6 +327,8 @@ where
                 inner: ArcSwap::new(Arc::new(HashMap::new())),
                 mutex: Mutex::new(()),
                 attrs: attrs.clone(),
+                overflow: OnceLock::new(),
+                cache: ThreadLocal::new(),
             },
         }
     }
@@ -291,9 +337,9 @@ where
 impl<const NAME: LabelName, T, U, M> ResolveLabels<(StringLabel<NAME, &U>,)>
     for WithLabel<StringLabel<NAME, T>, M>
 where
-    T: std::hash::Hash + Eq + Borrow<U> + ToString + Clone,
+    T: std::hash::Hash + Eq + Borrow<U> + ToString + Clone + Send + Sync + 'static,
     U: std::hash::Hash + Eq + ToOwned<Owned = T> + ?Sized,
-    M: Metric + 'static,
+    M: Metric + Send + Sync + 'static,
 {
     type Target = M;
 
@@ -301,22 +347,63 @@ where
         let label = label.0;
         let col = &self.collection;
 
-        if let Some(metric) = col.inner.load().get(label) {
-            return metric;
+        if let Some(entry) = col.cached(label) {
+            return &entry.metric;
+        }
+
+        let Some(entry) = col.resolve::<NAME, U>(label) else {
+            return col.overflow::<NAME>();
         };
 
-        let _guard = col.mutex.lock();
+        col.cache(entry);
+        &entry.metric
+    }
+}
 
-        let inner = col.inner.load();
+impl<T, M> StringCollection<T, M>
+where
+    T: std::hash::Hash + Eq + Clone + ToString + Send + Sync + 'static,
+    M: Metric + Send + Sync + 'static,
+{
+    /// Returns `None` if the label value is new and the collection is already
+    /// at its [`Attrs::max_string_labels`] capacity.
+    fn resolve<const NAME: LabelName, U>(&self, label: &U) -> Option<&'static StringEntry<T, M>>
+    where
+        T: Borrow<U>,
+        U: std::hash::Hash + Eq + ToOwned<Owned = T> + ?Sized,
+    {
+        let is_full = |inner: &HashMap<_, _>| {
+            self.attrs
+                .max_string_labels()
+                .is_some_and(|max| inner.len() >= max)
+        };
+
+        let inner = self.inner.load();
+        if let Some(entry) = inner.get(label) {
+            return Some(entry);
+        };
+
+        // Refuse without locking, the collection never shrinks.
+        if is_full(&inner) {
+            return None;
+        }
+
+        let _guard = self.mutex.lock();
+
+        let inner = self.inner.load();
 
         // In case if another thread has already initialized the metric while we were
         // waiting on the lock
-        if let Some(m) = inner.get(label) {
-            return m;
+        if let Some(entry) = inner.get(label) {
+            return Some(entry);
         };
 
+        if is_full(&inner) {
+            return None;
+        }
+
         // Copy-on-write
-        let m: &'static M = {
+        let entry: &'static StringEntry<T, M> = {
             // Make a deep copy of the `HashMap`.
             let mut inner_clone: HashMap<_, _> = (**inner).clone();
 
@@ -331,15 +418,63 @@ where
             // Leaking is fine here as this collection can only be used inside
             // `static` variables and there should be limited amount of label
             // values defined in runtime.
-            let m = Box::leak(Box::new(M::register(&col.attrs.with_label(label_))));
-            inner_clone.insert(label.to_owned(), m);
+            let entry = Box::leak(Box::new(StringEntry {
+                label: label.to_owned(),
+                metric: M::register(&self.attrs.with_label(label_)),
+            }));
+            inner_clone.insert(label.to_owned(), entry);
 
             // Write the updated `HashMap` into `ArcSwap`.
-            col.inner.store(Arc::new(inner_clone));
-            m
+            self.inner.store(Arc::new(inner_clone));
+            entry
         };
 
-        m
+        Some(entry)
+    }
+
+    /// Looks up the entry of the label value in the cache of the current
+    /// thread, moving it to the front.
+    fn cached<U>(&self, label: &U) -> Option<&'static StringEntry<T, M>>
+    where
+        T: Borrow<U>,
+        U: Eq + ?Sized,
+    {
+        let mut cache = self.cache.get_or_default().borrow_mut();
+
+        let pos = cache
+            .iter()
+            .position(|entry| entry.is_some_and(|entry| entry.label.borrow() == label))?;
+
+        cache[..=pos].rotate_right(1);
+        cache[0]
+    }
+
+    /// Puts the entry in front of the cache of the current thread, evicting the
+    /// least recently used one.
+    fn cache(&self, entry: &'static StringEntry<T, M>) {
+        let mut cache = self.cache.get_or_default().borrow_mut();
+
+        cache.rotate_right(1);
+        cache[0] = Some(entry);
+    }
+
+    /// Returns the metric shared by the refused label values, counting the
+    /// refusal.
+    fn overflow<const NAME: LabelName>(&self) -> &'static M {
+        let (metric, dropped) = self.overflow.get_or_init(|| {
+            let name = const { resolve_label_name::<NAME>() };
+            let label = Label::from_static_parts(name, OVERFLOW_LABEL_VALUE);
+
+            // Leaked only once per collection.
+            let metric: &'static M =
+                Box::leak(Box::new(M::register(&self.attrs.with_label(label))));
+            let dropped = metrics::counter!(DROPPED_LABELS_METRIC, "metric" => self.attrs.name());
+
+            (metric, dropped)
+        });
+
+        dropped.increment(1);
+        metric
     }
 }
 
@@ -481,6 +616,16 @@ pub type LabelName = u128;
 ///
 /// Required to hack around the lack of const `&'static str` generics in stable
 /// Rust.
+///
+/// The name must be a valid Prometheus label name (`[a-zA-Z_][a-zA-Z0-9_]*`),
+/// otherwise the compilation fails:
+///
+/// ```compile_fail
+/// use wc_metrics::{label_name, BoolLabel};
+///
+/// type MyLabel = BoolLabel<{ label_name("my-label") }>;
+/// # let _ = MyLabel::new(true);
+/// ```
 pub const fn label_name(s: &'static str) -> LabelName {
     let bytes = s.as_bytes();
 
@@ -489,6 +634,26 @@ pub const fn label_name(s: &'static str) -> LabelName {
         "`LabelName` should be no longer than 16 bytes"
     );
 
+    // loops aren't supported in const fns
+    const fn is_valid(idx: usize, bytes: &[u8]) -> bool {
+        if idx == bytes.len() {
+            return idx > 0;
+        }
+
+        let valid = match bytes[idx] {
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => true,
+            b'0'..=b'9' => idx > 0,
+            _ => false,
+        };
+
+        valid && is_valid(idx + 1, bytes)
+    }
+
+    assert!(
+        is_valid(0, bytes),
+        "`LabelName` should match `[a-zA-Z_][a-zA-Z0-9_]*`"
+    );
+
     // loops aren't supported in const fns
     const fn copy(idx: usize, src: &[u8], mut dst: [u8; 16]) -> [u8; 16] {
         if idx == src.len() {
@@ -542,4 +707,127 @@ fn test_label_name() {
     const A: LabelName = label_name("test");
     let name = const { resolve_label_name::<A>() };
     assert_eq!(name, "test");
+
+    const B: LabelName = label_name("_Shard_ID_2");
+    let name = const { resolve_label_name::<B>() };
+    assert_eq!(name, "_Shard_ID_2");
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        crate::{label_name, LabeledCounter, Lazy, StringLabel},
+        metrics_exporter_prometheus::PrometheusBuilder,
+    };
+
+    type Label = StringLabel<{ label_name("cached_label") }>;
+
+    static COUNTER: Lazy<LabeledCounter<Label>> = crate::new("cached_counter");
+
+    fn resolve(label: &str) -> *const metrics::Counter {
+        COUNTER.resolve_label(Label::new(label))
+    }
+
+    /// Label values cached by the current thread, most recently used first.
+    fn cached_labels() -> Vec<String> {
+        COUNTER
+            .get_or_register()
+            .collection
+            .cache
+            .get()
+            .map(|cache| {
+                cache
+                    .borrow()
+                    .iter()
+                    .flatten()
+                    .map(|e| e.label.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn string_label_cache() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || {
+            let a = resolve("a");
+            let b = resolve("b");
+            assert_ne!(a, b);
+            assert_eq!(cached_labels(), ["b", "a"]);
+
+            // Hot labels are resolved from the cache.
+            for _ in 0..10 {
+                assert_eq!(resolve("a"), a);
+                assert_eq!(resolve("b"), b);
+                COUNTER.increment(1u64, (Label::new("a"),));
+            }
+            assert_eq!(cached_labels(), ["b", "a"]);
+
+            // Evict "a" from the cache.
+            for label in ["c", "d", "e", "f"] {
+                assert_ne!(resolve(label), a);
+            }
+            assert_eq!(cached_labels(), ["f", "e", "d", "c"]);
+
+            // Evicted labels are still resolved into the same metrics.
+            assert_eq!(resolve("a"), a);
+            assert_eq!(cached_labels(), ["a", "f", "e", "d"]);
+            COUNTER.increment(1u64, (Label::new("a"),));
+        });
+
+        // Other threads resolve the same metrics, using their own caches.
+        let a_addr = resolve("a") as usize;
+        std::thread::spawn(move || {
+            assert!(cached_labels().is_empty());
+            assert_eq!(resolve("a") as usize, a_addr);
+            assert_eq!(cached_labels(), ["a"]);
+        })
+        .join()
+        .unwrap();
+
+        let rendered = handle.render();
+        assert!(rendered.contains(r#"cached_counter{cached_label="a"} 11"#));
+    }
+
+    #[test]
+    fn max_string_labels() {
+        type BoundedLabel = StringLabel<{ label_name("bounded_label") }, u32>;
+
+        static COUNTER: Lazy<LabeledCounter<BoundedLabel>> = crate::builder("bounded_counter")
+            .with_max_string_labels(2)
+            .build();
+
+        let resolve = |n: u32| COUNTER.resolve_label(BoundedLabel::new(&n)) as *const _;
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || {
+            let a = resolve(1);
+            let b = resolve(2);
+            assert_ne!(a, b);
+
+            // Values past the cap share the overflow metric.
+            let overflow = resolve(3);
+            assert_ne!(overflow, a);
+            assert_ne!(overflow, b);
+            for n in 4..100 {
+                assert_eq!(resolve(n), overflow);
+            }
+
+            // Already known values are still resolved.
+            assert_eq!(resolve(1), a);
+
+            COUNTER.increment(1u64, (BoundedLabel::new(&42),));
+        });
+
+        let len = COUNTER.get_or_register().collection.inner.load().len();
+        assert_eq!(len, 2);
+
+        let rendered = handle.render();
+        assert!(rendered.contains(r#"bounded_counter{bounded_label="__overflow__"} 1"#));
+        assert!(rendered.contains(r#"wc_metrics_dropped_labels{metric="bounded_counter"} 98"#));
+    }
 }
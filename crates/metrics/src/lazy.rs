@@ -2,12 +2,20 @@ use {
     crate::{
         label::{DynamicLabel, ResolveLabels, WithLabel},
         sealed::{Decrement, Execute, Increment, Record, Set},
+        toggle,
         Attrs,
+        EwmaRateCounter,
         Metric,
         StaticAttrs,
     },
     metrics::{Counter, Gauge, Histogram, IntoF64},
-    std::sync::OnceLock,
+    std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            OnceLock,
+        },
+        time::Instant,
+    },
 };
 
 /// Lazily initialized metric.
@@ -18,6 +26,7 @@ use {
 /// complile time and to build [`Lazy`] metrics.
 pub struct Lazy<M> {
     metric: OnceLock<M>,
+    disabled: OnceLock<&'static AtomicBool>,
     attrs: StaticAttrs,
 }
 
@@ -25,6 +34,7 @@ impl<M: Metric> Lazy<M> {
     pub(super) const fn new(attrs: StaticAttrs) -> Self {
         Self {
             metric: OnceLock::new(),
+            disabled: OnceLock::new(),
             attrs,
         }
     }
@@ -39,38 +49,103 @@ impl<M: Metric> Lazy<M> {
             dynamic: Default::default(),
         };
 
-        self.metric.get_or_init(|| M::register(&attrs))
+        self.metric.get_or_init(|| {
+            let _ = self.disabled.set(toggle::flag(self.attrs.name));
+            M::register(&attrs)
+        })
+    }
+
+    /// Registers the metric and emits its description without recording any
+    /// value, so the metric is exported right away.
+    ///
+    /// Intended to be called on startup, as otherwise the metrics are
+    /// registered on first use.
+    pub fn describe(&self) {
+        self.get_or_register();
+    }
+
+    /// Returns the metric, unless it's disabled using [`crate::disable`].
+    fn enabled(&self) -> Option<&M> {
+        let metric = self.get_or_register();
+
+        match self.disabled.get() {
+            Some(disabled) if disabled.load(Ordering::Relaxed) => None,
+            _ => Some(metric),
+        }
     }
 }
 
 impl Lazy<Counter> {
     /// See [`Counter::increment`].
     pub fn increment(&'static self, value: u64) {
-        self.get_or_register().increment(value)
+        if let Some(metric) = self.enabled() {
+            metric.increment(value);
+        }
+    }
+
+    /// Same as `increment`, but accepts a signed `value`, eg. a computed
+    /// delta.
+    ///
+    /// Negative values are ignored instead of wrapping around, and panic in
+    /// debug builds.
+    pub fn increment_checked(&'static self, value: i64) {
+        debug_assert!(value >= 0, "negative counter increment: {value}");
+
+        if let Ok(value) = u64::try_from(value) {
+            self.increment(value);
+        }
+    }
+}
+
+impl Lazy<EwmaRateCounter> {
+    /// See [`EwmaRateCounter::increment`].
+    pub fn increment(&'static self, value: u64) {
+        if let Some(metric) = self.enabled() {
+            metric.increment(value);
+        }
+    }
+
+    /// See [`EwmaRateCounter::rate`].
+    pub fn rate(&'static self) -> f64 {
+        self.get_or_register().rate()
     }
 }
 
 impl Lazy<Gauge> {
     /// See [`Gauge::increment`].
     pub fn increment<T: IntoF64>(&'static self, value: T) {
-        self.get_or_register().increment(value)
+        if let Some(metric) = self.enabled() {
+            metric.increment(value);
+        }
     }
 
     /// See [`Gauge::decrement`].
     pub fn decrement<T: IntoF64>(&'static self, value: T) {
-        self.get_or_register().decrement(value)
+        if let Some(metric) = self.enabled() {
+            metric.decrement(value);
+        }
     }
 
     /// See [`Gauge::set`].
     pub fn set<T: IntoF64>(&'static self, value: T) {
-        self.get_or_register().set(value)
+        if let Some(metric) = self.enabled() {
+            metric.set(value);
+        }
     }
 }
 
 impl Lazy<Histogram> {
     /// See [`Histogram::record`].
     pub fn record<T: IntoF64>(&'static self, value: T) {
-        self.get_or_register().record(value)
+        if let Some(metric) = self.enabled() {
+            metric.record(value);
+        }
+    }
+
+    /// Starts a [`HistogramTimer`] recording the elapsed time (in milliseconds)
+    /// into this histogram when dropped.
+    pub fn start_timer(&'static self) -> HistogramTimer {
+        HistogramTimer::new(self.enabled())
     }
 }
 
@@ -97,13 +172,45 @@ where
         self.get_or_register().resolve_labels(labels)
     }
 
+    /// Resolves multiple sets of dynamic labels at once, returning the
+    /// underlying metrics in the same order.
+    ///
+    /// Useful for resolving the metrics once outside of a hot loop and then
+    /// using the returned references directly.
+    pub fn resolve_all<LS>(
+        &'static self,
+        labels: impl IntoIterator<Item = LS>,
+    ) -> Vec<&'static <WithLabel<L, M> as ResolveLabels<LS>>::Target>
+    where
+        WithLabel<L, M>: Metric + ResolveLabels<LS>,
+    {
+        let metric = self.get_or_register();
+        labels
+            .into_iter()
+            .map(|labels| metric.resolve_labels(labels))
+            .collect()
+    }
+
     /// Calls [`Counter::increment`] or [`Gauge::increment`] on the metric built
     /// using the provided labels.
     pub fn increment<T, Labels>(&'static self, value: T, labels: Labels)
     where
         WithLabel<L, M>: Metric + Execute<Increment<T>, Labels>,
     {
-        self.get_or_register().execute(Increment(value), labels);
+        if let Some(metric) = self.enabled() {
+            metric.execute(Increment(value), labels);
+        }
+    }
+
+    /// Calls [`Counter::increment`] with `1` on the metric built using the
+    /// provided labels.
+    pub fn increment_one<Labels>(&'static self, labels: Labels)
+    where
+        WithLabel<L, M>: Metric + Execute<Increment<u64>, Labels>,
+    {
+        if let Some(metric) = self.enabled() {
+            metric.execute(Increment(1u64), labels);
+        }
     }
 
     /// Calls [`Gauge::decrement`] on the metric built using the provided
@@ -112,7 +219,9 @@ where
     where
         WithLabel<L, M>: Metric + Execute<Decrement<T>, Labels>,
     {
-        self.get_or_register().execute(Decrement(value), labels);
+        if let Some(metric) = self.enabled() {
+            metric.execute(Decrement(value), labels);
+        }
     }
 
     /// Calls [`Gauge::set`] on the metric built using the provided labels.
@@ -120,7 +229,20 @@ where
     where
         WithLabel<L, M>: Metric + Execute<Set<T>, Labels>,
     {
-        self.get_or_register().execute(Set(value), labels);
+        if let Some(metric) = self.enabled() {
+            metric.execute(Set(value), labels);
+        }
+    }
+
+    /// Starts a [`HistogramTimer`] recording the elapsed time (in milliseconds)
+    /// into the histogram built using the provided labels when dropped.
+    ///
+    /// The labels are resolved right away.
+    pub fn start_timer<LS>(&'static self, labels: LS) -> HistogramTimer
+    where
+        WithLabel<L, M>: Metric + ResolveLabels<LS, Target = Histogram>,
+    {
+        HistogramTimer::new(self.enabled().map(|metric| metric.resolve_labels(labels)))
     }
 
     /// Calls [`Histogram::record`] on the metric built using the provided
@@ -129,6 +251,223 @@ where
     where
         WithLabel<L, M>: Metric + Execute<Record<T>, Labels>,
     {
-        self.get_or_register().execute(Record(value), labels);
+        if let Some(metric) = self.enabled() {
+            metric.execute(Record(value), labels);
+        }
+    }
+}
+
+/// Guard recording the time elapsed since its creation (in milliseconds) into
+/// a [`Histogram`] when dropped.
+///
+/// Created by [`Lazy::start_timer`].
+#[must_use = "the elapsed time is recorded when the timer is dropped"]
+pub struct HistogramTimer {
+    histogram: Option<&'static Histogram>,
+    started_at: Instant,
+}
+
+impl HistogramTimer {
+    fn new(histogram: Option<&'static Histogram>) -> Self {
+        Self {
+            histogram,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Stops the timer, recording the elapsed time. Same as dropping it.
+    pub fn stop(self) {}
+}
+
+impl Drop for HistogramTimer {
+    fn drop(&mut self) {
+        if let Some(histogram) = self.histogram {
+            histogram.record(self.started_at.elapsed().as_secs_f64() * 1000.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        crate::{
+            enum_ordinalize::Ordinalize,
+            label_name,
+            BoolLabel,
+            Counter,
+            Enum,
+            EnumLabel,
+            Histogram,
+            LabeledCounter2,
+            LabeledHistogram,
+            Lazy,
+        },
+        metrics_exporter_prometheus::PrometheusBuilder,
+        std::{panic::AssertUnwindSafe, time::Duration},
+    };
+
+    #[derive(Clone, Copy, Ordinalize)]
+    enum Shard {
+        A,
+        B,
+    }
+
+    impl Enum for Shard {
+        fn as_str(&self) -> &'static str {
+            match self {
+                Self::A => "a",
+                Self::B => "b",
+            }
+        }
+    }
+
+    type ShardLabel = EnumLabel<{ label_name("shard") }, Shard>;
+    type FlagLabel = BoolLabel<{ label_name("flag") }>;
+
+    #[test]
+    fn resolve_all() {
+        static COUNTER: Lazy<LabeledCounter2<ShardLabel, FlagLabel>> =
+            crate::new("resolve_all_counter");
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        let combinations = [(Shard::A, false), (Shard::A, true), (Shard::B, true)];
+
+        let counters = metrics::with_local_recorder(&recorder, || {
+            COUNTER.resolve_all(
+                combinations
+                    .iter()
+                    .map(|&(shard, flag)| (ShardLabel::new(shard), FlagLabel::new(flag))),
+            )
+        });
+
+        assert_eq!(counters.len(), combinations.len());
+
+        for _ in 0..3 {
+            for (n, counter) in counters.iter().enumerate() {
+                counter.increment(n as u64 + 1);
+            }
+        }
+
+        let rendered = handle.render();
+
+        for (n, (shard, flag)) in combinations.into_iter().enumerate() {
+            let shard = format!("shard=\"{}\"", shard.as_str());
+            let flag = format!("flag=\"{flag}\"");
+            let value = format!(" {}", (n + 1) * 3);
+
+            assert!(
+                rendered
+                    .lines()
+                    .any(|line| line.starts_with("resolve_all_counter{")
+                        && line.contains(&shard)
+                        && line.contains(&flag)
+                        && line.ends_with(&value)),
+                "{rendered}"
+            );
+        }
+    }
+
+    #[test]
+    fn disable() {
+        static COUNTER_A: Lazy<Counter> = crate::new("disable_counter_a");
+        static COUNTER_B: Lazy<Counter> = crate::new("disable_counter_b");
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || {
+            COUNTER_A.increment(1);
+            COUNTER_B.increment(1);
+        });
+
+        crate::disable("disable_counter_a");
+
+        COUNTER_A.increment(1);
+        COUNTER_B.increment(1);
+
+        let rendered = handle.render();
+        assert!(rendered.contains("disable_counter_a 1\n"), "{rendered}");
+        assert!(rendered.contains("disable_counter_b 2\n"), "{rendered}");
+
+        crate::enable("disable_counter_a");
+
+        COUNTER_A.increment(1);
+
+        let rendered = handle.render();
+        assert!(rendered.contains("disable_counter_a 2\n"), "{rendered}");
+    }
+
+    #[test]
+    fn start_timer() {
+        static HISTOGRAM: Lazy<Histogram> = crate::new("timer_histogram");
+        static LABELED_HISTOGRAM: Lazy<LabeledHistogram<ShardLabel>> =
+            crate::new("timer_labeled_histogram");
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || {
+            let _timer = HISTOGRAM.start_timer();
+            let timer = LABELED_HISTOGRAM.start_timer((ShardLabel::new(Shard::B),));
+
+            std::thread::sleep(Duration::from_millis(50));
+            timer.stop();
+        });
+
+        let rendered = handle.render();
+
+        for prefix in [
+            "timer_histogram_sum ",
+            "timer_labeled_histogram_sum{shard=\"b\"} ",
+        ] {
+            let sum: f64 = rendered
+                .lines()
+                .find_map(|line| line.strip_prefix(prefix))
+                .unwrap_or_else(|| panic!("{prefix} missing: {rendered}"))
+                .parse()
+                .unwrap();
+
+            assert!((50.0..1000.0).contains(&sum), "{sum}");
+        }
+    }
+
+    #[test]
+    fn describe() {
+        static COUNTER: Lazy<Counter> = crate::builder("describe_counter")
+            .with_description("Described counter")
+            .build();
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || COUNTER.describe());
+
+        let rendered = handle.render();
+        assert!(
+            rendered.contains("# HELP describe_counter Described counter\n"),
+            "{rendered}"
+        );
+        assert!(rendered.contains("describe_counter 0\n"), "{rendered}");
+    }
+
+    #[test]
+    fn increment_checked() {
+        static COUNTER: Lazy<Counter> = crate::new("increment_checked_counter");
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || COUNTER.increment_checked(3));
+
+        let negative = std::panic::catch_unwind(AssertUnwindSafe(|| COUNTER.increment_checked(-1)));
+        assert_eq!(negative.is_err(), cfg!(debug_assertions));
+
+        let rendered = handle.render();
+        assert!(
+            rendered.contains("increment_checked_counter 3\n"),
+            "{rendered}"
+        );
     }
 }
@@ -83,20 +83,42 @@
 //! ```
 
 pub use {
+    buckets::Buckets,
     enum_ordinalize,
-    label::{label_name, BoolLabel, Enum, EnumLabel, LabelName, Optional, StringLabel, WithLabel},
-    lazy::Lazy,
+    ewma::{EwmaRateCounter, DEFAULT_EWMA_ALPHA, DEFAULT_EWMA_INTERVAL},
+    group::HistogramGroup,
+    label::{
+        label_name,
+        BoolLabel,
+        Enum,
+        EnumLabel,
+        LabelName,
+        Optional,
+        StringLabel,
+        WithLabel,
+        DROPPED_LABELS_METRIC,
+        OVERFLOW_LABEL_VALUE,
+    },
+    lazy::{HistogramTimer, Lazy},
     metrics::{self as backend, Counter, Gauge, Histogram},
+    outcome::{Outcome, OutcomeLabel},
+    toggle::{disable, enable},
 };
 use {
     label::{DynamicLabels, Labeled, Labeled2, Labeled3, Labeled4, StaticLabels},
     metrics::{IntoF64, Label},
     sealed::{Attrs, Decrement, Execute, Increment, Metric, Record, Set},
+    std::time::Duration,
 };
 
+mod buckets;
+mod ewma;
+mod group;
 mod label;
 mod lazy;
 mod macros;
+mod outcome;
+mod toggle;
 
 #[cfg(test)]
 mod examples;
@@ -127,6 +149,8 @@ pub const fn builder(name: &'static str) -> Builder {
             name,
             description: None,
             labels: &[],
+            max_string_labels: None,
+            ewma: None,
         },
     }
 }
@@ -157,6 +181,26 @@ impl Builder {
         self
     }
 
+    /// Specifies the `alpha` and the sampling `interval` of the
+    /// [`EwmaRateCounter`].
+    ///
+    /// [`DEFAULT_EWMA_ALPHA`] and [`DEFAULT_EWMA_INTERVAL`] are used otherwise.
+    pub const fn with_ewma(mut self, alpha: f64, interval: Duration) -> Self {
+        self.attrs.ewma = Some((alpha, interval));
+        self
+    }
+
+    /// Caps the number of distinct values of each [`StringLabel`] of the
+    /// metric.
+    ///
+    /// Values past the cap are refused: they resolve into a shared metric with
+    /// the [`OVERFLOW_LABEL_VALUE`] label value instead, and increment the
+    /// [`DROPPED_LABELS_METRIC`] counter.
+    pub const fn with_max_string_labels(mut self, max: usize) -> Self {
+        self.attrs.max_string_labels = Some(max);
+        self
+    }
+
     /// Builds the [`Lazy`] metric.
     pub const fn build<M: Metric>(self) -> Lazy<M> {
         Lazy::new(self.attrs)
@@ -172,6 +216,14 @@ impl Attrs {
         self.static_.description
     }
 
+    fn max_string_labels(&self) -> Option<usize> {
+        self.static_.max_string_labels
+    }
+
+    fn ewma(&self) -> Option<(f64, Duration)> {
+        self.static_.ewma
+    }
+
     fn labels(&self) -> DynamicLabels {
         let mut labels = self.dynamic.labels.clone();
         let static_ = self.static_.labels.iter();
@@ -191,6 +243,8 @@ struct StaticAttrs {
     name: &'static str,
     description: Option<&'static str>,
     labels: StaticLabels,
+    max_string_labels: Option<usize>,
+    ewma: Option<(f64, Duration)>,
 }
 
 #[derive(Clone, Debug, Default)]
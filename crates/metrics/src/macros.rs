@@ -172,3 +172,38 @@ macro_rules! resolve_labels {
     };
     ( $var:ident, $( $label_name:literal => $label_value:literal ),+ ) => {};
 }
+
+/// Times a fallible operation, recording its duration into the
+/// `{name}_duration` histogram and incrementing the `{name}_count` counter,
+/// both labeled by the [`Outcome`](crate::Outcome) of the operation. Evaluates
+/// to the `Result` of the operation.
+///
+/// Early returns (eg. via `?`) from the operation block skip the recording.
+///
+/// Usage:
+/// ```
+/// fn parse(s: &str) -> Result<u32, std::num::ParseIntError> {
+///     wc_metrics::observe_op!("parse", { s.parse() })
+/// }
+///
+/// assert_eq!(parse("42"), Ok(42));
+/// assert!(parse("abc").is_err());
+/// ```
+#[macro_export]
+macro_rules! observe_op {
+    ($name:literal, $op:expr) => {{
+        static DURATION: $crate::Lazy<$crate::LabeledHistogram<$crate::OutcomeLabel>> =
+            $crate::new(concat!($name, "_duration"));
+        static COUNT: $crate::Lazy<$crate::LabeledCounter<$crate::OutcomeLabel>> =
+            $crate::new(concat!($name, "_count"));
+
+        let started_at = ::std::time::Instant::now();
+        let result = $op;
+        let outcome = $crate::OutcomeLabel::new($crate::Outcome::of(&result));
+
+        DURATION.record(started_at.elapsed(), (outcome,));
+        COUNT.increment(1u64, (outcome,));
+
+        result
+    }};
+}
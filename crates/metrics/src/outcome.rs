@@ -0,0 +1,69 @@
+use {
+    crate::{label_name, Enum, EnumLabel},
+    enum_ordinalize::Ordinalize,
+};
+
+/// Outcome of a fallible operation, used by [`observe_op`](crate::observe_op).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Ordinalize)]
+pub enum Outcome {
+    Ok,
+    Err,
+}
+
+pub type OutcomeLabel = EnumLabel<{ label_name("outcome") }, Outcome>;
+
+impl Outcome {
+    pub fn of<T, E>(result: &Result<T, E>) -> Self {
+        match result {
+            Ok(_) => Self::Ok,
+            Err(_) => Self::Err,
+        }
+    }
+}
+
+impl Enum for Outcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Err => "err",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use metrics_exporter_prometheus::PrometheusBuilder;
+
+    fn op(fail: bool) -> Result<u32, &'static str> {
+        crate::observe_op!("observe_op", {
+            if fail {
+                Err("failed")
+            } else {
+                Ok(42)
+            }
+        })
+    }
+
+    #[test]
+    fn observe_op() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || {
+            assert_eq!(op(false), Ok(42));
+            assert_eq!(op(false), Ok(42));
+            assert_eq!(op(true), Err("failed"));
+        });
+
+        let rendered = handle.render();
+
+        for series in [
+            r#"observe_op_count{outcome="ok"} 2"#,
+            r#"observe_op_count{outcome="err"} 1"#,
+            r#"observe_op_duration_count{outcome="ok"} 2"#,
+            r#"observe_op_duration_count{outcome="err"} 1"#,
+        ] {
+            assert!(rendered.contains(series), "{rendered}");
+        }
+    }
+}
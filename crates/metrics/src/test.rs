@@ -97,11 +97,24 @@ impl Metrics {
     }
 
     fn assert_counters(&mut self, value: u64) {
-        self.assert_metrics("counter", Value::Counter(value as f64))
+        let value = Value::Counter(value as f64);
+        self.assert_metric("counter21", None, &[("e", "a")], &value);
+        self.assert_metric("counter22", None, &[("e", "a")], &value);
+        self.assert_metric("counter23", None, &[("chain_id", "137")], &value);
+
+        let labels = &[("port", "8080"), ("st", "1")];
+        self.assert_metric("counter24", Some("description24"), labels, &value);
+        self.assert_metrics("counter", value)
     }
 
     fn assert_gauges(&mut self, value: f64) {
-        self.assert_metrics("gauge", Value::Gauge(value))
+        let value = Value::Gauge(value);
+        self.assert_metric("gauge21", None, &[("chain_id", "137")], &value);
+
+        let labels = &[("port", "8080"), ("st", "1")];
+        self.assert_metric("gauge22", Some("description22"), labels, &value);
+
+        self.assert_metrics("gauge", value)
     }
 
     fn assert_histograms(&mut self, count: f64) {
@@ -204,6 +217,7 @@ impl Metrics {
         self.assert_future_metrics_(name::FUTURES_STARTED, Value::Counter(count));
         self.assert_future_metrics_(name::FUTURES_FINISHED, Value::Counter(count));
         self.assert_future_metrics_(name::FUTURE_POLLS, Value::Counter(count));
+        self.assert_future_metrics_(name::FUTURES_IN_FLIGHT, Value::Gauge(0.0));
 
         self.assert_future_metrics_(name::FUTURE_DURATION, expected_histogram(count));
         self.assert_future_metrics_(name::FUTURE_POLL_DURATION, expected_histogram(count));
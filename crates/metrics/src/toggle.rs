@@ -0,0 +1,44 @@
+//! Runtime switches for disabling [`Lazy`](crate::Lazy) metrics by name.
+
+use {
+    parking_lot::Mutex,
+    std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            OnceLock,
+        },
+    },
+};
+
+/// Flags of all the metric names ever registered or toggled. The flags are
+/// leaked, so [`Lazy`](crate::Lazy) metrics can hold onto them and check them
+/// without any locking.
+static FLAGS: OnceLock<Mutex<HashMap<String, &'static AtomicBool>>> = OnceLock::new();
+
+/// Disables all the metrics with the specified `name`, making their reporting
+/// a no-op until [`enable`] is called.
+///
+/// Only affects the values reported via the methods of [`Lazy`](crate::Lazy)
+/// itself, not the metrics obtained from it (eg. via `resolve_labels`).
+pub fn disable(name: &str) {
+    flag(name).store(true, Ordering::Relaxed);
+}
+
+/// Re-enables the metrics previously disabled using [`disable`].
+pub fn enable(name: &str) {
+    flag(name).store(false, Ordering::Relaxed);
+}
+
+/// Returns the "disabled" flag of the metrics with the specified `name`.
+pub(crate) fn flag(name: &str) -> &'static AtomicBool {
+    let mut flags = FLAGS.get_or_init(Default::default).lock();
+
+    if let Some(flag) = flags.get(name) {
+        return flag;
+    }
+
+    let flag = Box::leak(Box::new(AtomicBool::new(false)));
+    flags.insert(name.to_owned(), flag);
+    flag
+}
@@ -1,17 +1,40 @@
 use {
     chrono::{DateTime, Duration, Utc},
-    deadpool_redis::{Pool, PoolError},
+    deadpool_redis::{Connection, Pool, PoolError},
     moka::future::Cache,
     redis::{RedisError, Script},
-    std::{collections::HashMap, sync::Arc},
+    std::{
+        collections::HashMap,
+        sync::{Arc, OnceLock},
+    },
 };
 
+// The scripts are shared, so their SHA1 digests are only computed once. The
+// script body is only sent if redis responds with `NOSCRIPT` to `EVALSHA`.
+static TOKEN_BUCKET_SCRIPT: OnceLock<Script> = OnceLock::new();
+static SLIDING_WINDOW_SCRIPT: OnceLock<Script> = OnceLock::new();
+
 #[derive(Debug, thiserror::Error)]
 #[error("Rate limit exceeded. Try again at {reset}")]
 pub struct RateLimitExceeded {
     reset: u64,
 }
 
+/// Outcome of a successful [`token_bucket`] check, e.g. for populating the
+/// `X-RateLimit-*` response headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitResult {
+    /// Number of tokens remaining in the bucket.
+    pub remaining: u64,
+
+    /// Unix timestamp (in seconds) at which the next token is going to be
+    /// refilled.
+    pub reset: u64,
+
+    /// Maximum number of tokens in the bucket.
+    pub limit: u32,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum InternalRateLimitError {
     #[error("Redis pool error {0}")]
@@ -30,6 +53,107 @@ pub enum RateLimitError {
     Internal(InternalRateLimitError),
 }
 
+/// Configuration of a [`RateLimiter`].
+#[derive(Debug, Clone)]
+pub struct RateLimiterConfig {
+    /// Prefix added to the keys, isolating the limiters sharing a redis
+    /// instance.
+    pub namespace: Option<String>,
+
+    /// Maximum number of tokens in the bucket.
+    pub max_tokens: u32,
+
+    /// Token refill interval.
+    pub interval: Duration,
+
+    /// Number of tokens refilled each interval.
+    pub refill_rate: u32,
+
+    /// Number of tokens new buckets start with, allowing a one-off burst above
+    /// `max_tokens`. Defaults to `max_tokens`. See
+    /// [`token_bucket_many_with_burst`].
+    pub burst: Option<u32>,
+}
+
+/// Token bucket rate limiter holding the redis pool, configuration and the
+/// in-memory cache of rate-limited keys.
+#[derive(Clone)]
+pub struct RateLimiter {
+    redis_write_pool: Arc<Pool>,
+    config: RateLimiterConfig,
+    mem_cache: Cache<String, u64>,
+}
+
+impl RateLimiter {
+    pub fn new(redis_write_pool: Arc<Pool>, config: RateLimiterConfig) -> Self {
+        let mem_cache = Cache::builder()
+            .time_to_live(config.interval.to_std().unwrap_or_default())
+            .build();
+
+        Self {
+            redis_write_pool,
+            config,
+            mem_cache,
+        }
+    }
+
+    /// Rate limit check of one key. See [`token_bucket`].
+    pub async fn check(&self, key: &str) -> Result<RateLimitResult, RateLimitError> {
+        token_bucket_with_burst(
+            &self.mem_cache,
+            &self.redis_write_pool,
+            self.namespaced(key),
+            self.config.max_tokens,
+            self.burst(),
+            self.config.interval,
+            self.config.refill_rate,
+            Utc::now(),
+        )
+        .await
+    }
+
+    /// Rate limit check of many keys. See [`token_bucket_many_cached`].
+    ///
+    /// The keys of the returned map are not namespaced.
+    pub async fn check_many(
+        &self,
+        keys: &[&str],
+    ) -> Result<HashMap<String, (i64, u64)>, InternalRateLimitError> {
+        let namespaced = keys.iter().map(|key| self.namespaced(key)).collect();
+
+        let mut results = token_bucket_many_cached_with_burst(
+            &self.mem_cache,
+            &self.redis_write_pool,
+            namespaced,
+            self.config.max_tokens,
+            self.burst(),
+            self.config.interval,
+            self.config.refill_rate,
+            Utc::now(),
+        )
+        .await?;
+
+        Ok(keys
+            .iter()
+            .filter_map(|key| {
+                let result = results.remove(&self.namespaced(key))?;
+                Some((key.to_string(), result))
+            })
+            .collect())
+    }
+
+    fn burst(&self) -> u32 {
+        self.config.burst.unwrap_or(self.config.max_tokens)
+    }
+
+    fn namespaced(&self, key: &str) -> String {
+        match &self.config.namespace {
+            Some(namespace) => format!("{namespace}:{key}"),
+            None => key.to_owned(),
+        }
+    }
+}
+
 /// Rate limit check using a token bucket algorithm for one key and in-memory
 /// cache for rate-limited keys. `mem_cache` TTL must be set to the same value
 /// as the refill interval.
@@ -41,7 +165,33 @@ pub async fn token_bucket(
     interval: Duration,
     refill_rate: u32,
     now_millis: DateTime<Utc>,
-) -> Result<(), RateLimitError> {
+) -> Result<RateLimitResult, RateLimitError> {
+    token_bucket_with_burst(
+        mem_cache,
+        redis_write_pool,
+        key,
+        max_tokens,
+        max_tokens,
+        interval,
+        refill_rate,
+        now_millis,
+    )
+    .await
+}
+
+/// Same as [`token_bucket`], but new buckets start with `burst` tokens. See
+/// [`token_bucket_many_with_burst`].
+#[allow(clippy::too_many_arguments)]
+pub async fn token_bucket_with_burst(
+    mem_cache: &Cache<String, u64>,
+    redis_write_pool: &Arc<Pool>,
+    key: String,
+    max_tokens: u32,
+    burst: u32,
+    interval: Duration,
+    refill_rate: u32,
+    now_millis: DateTime<Utc>,
+) -> Result<RateLimitResult, RateLimitError> {
     // Check if the key is in the memory cache of rate limited keys
     // to omit the redis RTT in case of flood
     if let Some(reset) = mem_cache.get(&key).await {
@@ -50,10 +200,11 @@ pub async fn token_bucket(
         }));
     }
 
-    let result = token_bucket_many(
+    let result = token_bucket_many_with_burst(
         redis_write_pool,
         vec![key.clone()],
         max_tokens,
+        burst,
         interval,
         refill_rate,
         now_millis,
@@ -73,7 +224,11 @@ pub async fn token_bucket(
             reset: reset_interval,
         }))
     } else {
-        Ok(())
+        Ok(RateLimitResult {
+            remaining: *remaining as u64,
+            reset: reset / 1000,
+            limit: max_tokens,
+        })
     }
 }
 
@@ -85,16 +240,268 @@ pub async fn token_bucket_many(
     interval: Duration,
     refill_rate: u32,
     now_millis: DateTime<Utc>,
+) -> Result<HashMap<String, (i64, u64)>, InternalRateLimitError> {
+    token_bucket_many_with_burst(
+        redis_write_pool,
+        keys,
+        max_tokens,
+        max_tokens,
+        interval,
+        refill_rate,
+        now_millis,
+    )
+    .await
+}
+
+/// Same as [`token_bucket_many`], but uses the provided connection instead of
+/// acquiring one from the pool.
+pub async fn token_bucket_many_with_conn(
+    conn: &mut Connection,
+    keys: Vec<String>,
+    max_tokens: u32,
+    interval: Duration,
+    refill_rate: u32,
+    now_millis: DateTime<Utc>,
+) -> Result<HashMap<String, (i64, u64)>, InternalRateLimitError> {
+    token_bucket_many_with_burst_and_conn(
+        conn,
+        keys,
+        max_tokens,
+        max_tokens,
+        interval,
+        refill_rate,
+        now_millis,
+    )
+    .await
+}
+
+/// Same as [`token_bucket_many`], but new buckets start with `burst` tokens
+/// instead of `max_tokens`, allowing a one-off burst above the steady rate.
+///
+/// Refills never exceed `max_tokens`, so the burst is only restored once the
+/// bucket is left idle long enough to expire. A `burst` lower than
+/// `max_tokens` is treated as `max_tokens`.
+pub async fn token_bucket_many_with_burst(
+    redis_write_pool: &Arc<Pool>,
+    keys: Vec<String>,
+    max_tokens: u32,
+    burst: u32,
+    interval: Duration,
+    refill_rate: u32,
+    now_millis: DateTime<Utc>,
+) -> Result<HashMap<String, (i64, u64)>, InternalRateLimitError> {
+    let mut conn = redis_write_pool
+        .clone()
+        .get()
+        .await
+        .map_err(InternalRateLimitError::Pool)?;
+
+    token_bucket_many_with_burst_and_conn(
+        &mut conn,
+        keys,
+        max_tokens,
+        burst,
+        interval,
+        refill_rate,
+        now_millis,
+    )
+    .await
+}
+
+/// Same as [`token_bucket_many_with_burst`], but uses the provided connection
+/// instead of acquiring one from the pool.
+pub async fn token_bucket_many_with_burst_and_conn(
+    conn: &mut Connection,
+    keys: Vec<String>,
+    max_tokens: u32,
+    burst: u32,
+    interval: Duration,
+    refill_rate: u32,
+    now_millis: DateTime<Utc>,
+) -> Result<HashMap<String, (i64, u64)>, InternalRateLimitError> {
+    token_bucket_script(
+        conn,
+        keys,
+        max_tokens,
+        burst.max(max_tokens),
+        interval,
+        refill_rate,
+        now_millis,
+    )
+    .await
+}
+
+async fn token_bucket_script(
+    conn: &mut Connection,
+    keys: Vec<String>,
+    max_tokens: u32,
+    burst: u32,
+    interval: Duration,
+    refill_rate: u32,
+    now_millis: DateTime<Utc>,
 ) -> Result<HashMap<String, (i64, u64)>, InternalRateLimitError> {
     // Remaining is number of tokens remaining. -1 for rate limited.
     // Reset is the time at which there will be 1 more token than before. This
     // could, for example, be used to cache a 0 token count.
-    Script::new(include_str!("token_bucket.lua"))
+    TOKEN_BUCKET_SCRIPT
+        .get_or_init(|| Script::new(include_str!("token_bucket.lua")))
         .key(keys)
         .arg(max_tokens)
         .arg(interval.num_milliseconds())
         .arg(refill_rate)
         .arg(now_millis.timestamp_millis())
+        .arg(burst)
+        .invoke_async::<_, String>(conn)
+        .await
+        .map_err(InternalRateLimitError::Redis)
+        .map(|value| serde_json::from_str(&value).expect("Redis script should return valid JSON"))
+}
+
+/// Same as [`token_bucket_many`], but skips the redis RTT for the keys found in
+/// the in-memory cache of rate-limited keys, reporting them as exceeded with
+/// the cached reset. `mem_cache` TTL must be set to the same value as the
+/// refill interval.
+pub async fn token_bucket_many_cached(
+    mem_cache: &Cache<String, u64>,
+    redis_write_pool: &Arc<Pool>,
+    keys: Vec<String>,
+    max_tokens: u32,
+    interval: Duration,
+    refill_rate: u32,
+    now_millis: DateTime<Utc>,
+) -> Result<HashMap<String, (i64, u64)>, InternalRateLimitError> {
+    token_bucket_many_cached_with_burst(
+        mem_cache,
+        redis_write_pool,
+        keys,
+        max_tokens,
+        max_tokens,
+        interval,
+        refill_rate,
+        now_millis,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn token_bucket_many_cached_with_burst(
+    mem_cache: &Cache<String, u64>,
+    redis_write_pool: &Arc<Pool>,
+    keys: Vec<String>,
+    max_tokens: u32,
+    burst: u32,
+    interval: Duration,
+    refill_rate: u32,
+    now_millis: DateTime<Utc>,
+) -> Result<HashMap<String, (i64, u64)>, InternalRateLimitError> {
+    let mut results = HashMap::with_capacity(keys.len());
+    let mut uncached = Vec::with_capacity(keys.len());
+
+    for key in keys {
+        match mem_cache.get(&key).await {
+            // The cache stores the reset in seconds.
+            Some(reset) => {
+                results.insert(key, (-1, reset * 1000));
+            }
+            None => uncached.push(key),
+        }
+    }
+
+    if uncached.is_empty() {
+        return Ok(results);
+    }
+
+    let fetched = token_bucket_many_with_burst(
+        redis_write_pool,
+        uncached,
+        max_tokens,
+        burst,
+        interval,
+        refill_rate,
+        now_millis,
+    )
+    .await?;
+
+    for (key, (remaining, reset)) in fetched {
+        if remaining.is_negative() {
+            // Insert the rate-limited key into the memory cache to avoid the redis RTT in
+            // case of flood
+            mem_cache.insert(key.clone(), reset / 1000).await;
+        }
+
+        results.insert(key, (remaining, reset));
+    }
+
+    Ok(results)
+}
+
+/// Rate limit check using a sliding window log algorithm for one key and
+/// in-memory cache for rate-limited keys. Unlike [`token_bucket`] doesn't allow
+/// bursts exceeding `max_requests` within any `window`. `mem_cache` TTL must be
+/// set to the same value as the window.
+pub async fn sliding_window(
+    mem_cache: &Cache<String, u64>,
+    redis_write_pool: &Arc<Pool>,
+    key: String,
+    max_requests: u32,
+    window: Duration,
+    now_millis: DateTime<Utc>,
+) -> Result<RateLimitResult, RateLimitError> {
+    // Check if the key is in the memory cache of rate limited keys
+    // to omit the redis RTT in case of flood
+    if let Some(reset) = mem_cache.get(&key).await {
+        return Err(RateLimitError::RateLimitExceeded(RateLimitExceeded {
+            reset,
+        }));
+    }
+
+    let result = sliding_window_many(
+        redis_write_pool,
+        vec![key.clone()],
+        max_requests,
+        window,
+        now_millis,
+    )
+    .await
+    .map_err(RateLimitError::Internal)?;
+
+    let (count, reset) = result.get(&key).expect("Should contain the key");
+    let reset = reset / 1000;
+
+    if count.is_negative() {
+        // Insert the rate-limited key into the memory cache to avoid the redis RTT in
+        // case of flood
+        mem_cache.insert(key, reset).await;
+
+        Err(RateLimitError::RateLimitExceeded(RateLimitExceeded {
+            reset,
+        }))
+    } else {
+        Ok(RateLimitResult {
+            remaining: max_requests.saturating_sub(*count as u32) as u64,
+            reset,
+            limit: max_requests,
+        })
+    }
+}
+
+/// Rate limit check using a sliding window log algorithm for many keys.
+pub async fn sliding_window_many(
+    redis_write_pool: &Arc<Pool>,
+    keys: Vec<String>,
+    max_requests: u32,
+    window: Duration,
+    now_millis: DateTime<Utc>,
+) -> Result<HashMap<String, (i64, u64)>, InternalRateLimitError> {
+    // Count is the number of requests in the current window, including this one.
+    // -1 for rate limited.
+    // Reset is the time at which the oldest request in the window expires.
+    SLIDING_WINDOW_SCRIPT
+        .get_or_init(|| Script::new(include_str!("sliding_window.lua")))
+        .key(keys)
+        .arg(max_requests)
+        .arg(window.num_milliseconds())
+        .arg(now_millis.timestamp_millis())
         .invoke_async::<_, String>(
             &mut redis_write_pool
                 .clone()
@@ -276,4 +683,412 @@ mod tests {
         // Clear keys after the test
         redis_clear_keys(REDIS_URI, &[key.clone()]).await;
     }
+
+    #[tokio::test]
+    async fn test_token_bucket_remaining() {
+        let cache: Cache<String, u64> = Cache::builder()
+            .time_to_live(std::time::Duration::from_millis(
+                REFILL_INTERVAL_MILLIS as u64,
+            ))
+            .build();
+
+        let cfg = Config::from_url(REDIS_URI);
+        let pool = Arc::new(cfg.create_pool(Some(Runtime::Tokio1)).unwrap());
+        let key = Uuid::new_v4().to_string();
+
+        // Before running the test, ensure the test keys are cleared
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+
+        let refill_interval = chrono::Duration::try_milliseconds(REFILL_INTERVAL_MILLIS).unwrap();
+        let now_millis = Utc::now();
+
+        for i in 1..=MAX_TOKENS {
+            let result = token_bucket(
+                &cache,
+                &pool,
+                key.clone(),
+                MAX_TOKENS,
+                refill_interval,
+                REFILL_RATE,
+                now_millis,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(result.remaining, (MAX_TOKENS - i) as u64);
+            assert_eq!(result.limit, MAX_TOKENS);
+            assert_eq!(
+                result.reset,
+                (now_millis.timestamp_millis() + REFILL_INTERVAL_MILLIS) as u64 / 1000
+            );
+        }
+
+        // Clear keys after the test
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+    }
+
+    #[tokio::test]
+    async fn test_token_bucket_many_with_conn() {
+        let cfg = Config::from_url(REDIS_URI);
+        let pool = Arc::new(cfg.create_pool(Some(Runtime::Tokio1)).unwrap());
+        let mut conn = pool.get().await.unwrap();
+        let key = Uuid::new_v4().to_string();
+
+        // Before running the test, ensure the test keys are cleared
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+
+        let refill_interval = chrono::Duration::try_milliseconds(REFILL_INTERVAL_MILLIS).unwrap();
+        let now_millis = Utc::now();
+        let reset = (now_millis.timestamp_millis() + REFILL_INTERVAL_MILLIS) as u64;
+
+        // The same connection is reused for all the checks.
+        for i in 1..=MAX_TOKENS {
+            let result = token_bucket_many_with_conn(
+                &mut conn,
+                vec![key.clone()],
+                MAX_TOKENS,
+                refill_interval,
+                REFILL_RATE,
+                now_millis,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(result[&key], ((MAX_TOKENS - i) as i64, reset));
+        }
+
+        let result = token_bucket_many_with_conn(
+            &mut conn,
+            vec![key.clone()],
+            MAX_TOKENS,
+            refill_interval,
+            REFILL_RATE,
+            now_millis,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result[&key], (-1, reset));
+
+        // Clear keys after the test
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+    }
+
+    #[tokio::test]
+    async fn test_token_bucket_many_with_burst() {
+        const BURST: u32 = 8;
+
+        let cfg = Config::from_url(REDIS_URI);
+        let pool = Arc::new(cfg.create_pool(Some(Runtime::Tokio1)).unwrap());
+        let key = Uuid::new_v4().to_string();
+
+        // Before running the test, ensure the test keys are cleared
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+
+        let refill_interval = chrono::Duration::try_milliseconds(REFILL_INTERVAL_MILLIS).unwrap();
+        let rate_limit = |now_millis| {
+            let key = key.clone();
+            let pool = pool.clone();
+            async move {
+                token_bucket_many_with_burst(
+                    &pool,
+                    vec![key.clone()],
+                    MAX_TOKENS,
+                    BURST,
+                    refill_interval,
+                    REFILL_RATE,
+                    now_millis,
+                )
+                .await
+                .unwrap()[&key]
+                    .0
+            }
+        };
+
+        // The burst above `MAX_TOKENS` is allowed once.
+        let now_millis = Utc::now();
+        for i in 1..=BURST {
+            assert_eq!(rate_limit(now_millis).await, (BURST - i) as i64);
+        }
+        assert_eq!(rate_limit(now_millis).await, -1);
+
+        // Afterwards the bucket is only refilled up to `MAX_TOKENS`.
+        let now_millis = now_millis + refill_interval * BURST as i32;
+        for i in 1..=MAX_TOKENS {
+            assert_eq!(rate_limit(now_millis).await, (MAX_TOKENS - i) as i64);
+        }
+        assert_eq!(rate_limit(now_millis).await, -1);
+
+        // Clear keys after the test
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+    }
+
+    #[tokio::test]
+    async fn test_shared_script() {
+        let cfg = Config::from_url(REDIS_URI);
+        let pool = Arc::new(cfg.create_pool(Some(Runtime::Tokio1)).unwrap());
+        let key = Uuid::new_v4().to_string();
+
+        // Before running the test, ensure the test keys are cleared
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+
+        let refill_interval = chrono::Duration::try_milliseconds(REFILL_INTERVAL_MILLIS).unwrap();
+        let now_millis = Utc::now();
+        let rate_limit = || {
+            token_bucket_many(
+                &pool,
+                vec![key.clone()],
+                MAX_TOKENS,
+                refill_interval,
+                REFILL_RATE,
+                now_millis,
+            )
+        };
+
+        async fn script_exists(conn: &mut redis::aio::Connection) -> bool {
+            redis::cmd("SCRIPT")
+                .arg("EXISTS")
+                .arg(TOKEN_BUCKET_SCRIPT.get().unwrap().get_hash())
+                .query_async::<_, Vec<bool>>(conn)
+                .await
+                .unwrap()[0]
+        }
+
+        let client = redis::Client::open(REDIS_URI).unwrap();
+        let mut conn = client.get_async_connection().await.unwrap();
+
+        assert_eq!(rate_limit().await.unwrap()[&key].0, (MAX_TOKENS - 1) as i64);
+        assert!(script_exists(&mut conn).await);
+
+        // The script is reloaded if redis no longer has it.
+        let _: () = redis::cmd("SCRIPT")
+            .arg("FLUSH")
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+
+        assert_eq!(rate_limit().await.unwrap()[&key].0, (MAX_TOKENS - 2) as i64);
+        assert!(script_exists(&mut conn).await);
+
+        // Clear keys after the test
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+    }
+
+    #[tokio::test]
+    async fn test_token_bucket_many_cached() {
+        let cache: Cache<String, u64> = Cache::builder()
+            .time_to_live(std::time::Duration::from_millis(
+                REFILL_INTERVAL_MILLIS as u64,
+            ))
+            .build();
+
+        let keys = (0..3)
+            .map(|_| Uuid::new_v4().to_string())
+            .collect::<Vec<String>>();
+
+        for (reset, key) in keys.iter().enumerate() {
+            cache.insert(key.clone(), reset as u64).await;
+        }
+
+        // Any redis request would fail, as nothing is listening on this port.
+        let cfg = Config::from_url("redis://localhost:1");
+        let pool = Arc::new(cfg.create_pool(Some(Runtime::Tokio1)).unwrap());
+        let refill_interval = chrono::Duration::try_milliseconds(REFILL_INTERVAL_MILLIS).unwrap();
+
+        let result = token_bucket_many_cached(
+            &cache,
+            &pool,
+            keys.clone(),
+            MAX_TOKENS,
+            refill_interval,
+            REFILL_RATE,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.len(), keys.len());
+        for (reset, key) in keys.iter().enumerate() {
+            assert_eq!(result[key], (-1, reset as u64 * 1000));
+        }
+
+        // An uncached key does reach redis.
+        let mut keys = keys;
+        keys.push(Uuid::new_v4().to_string());
+
+        let result = token_bucket_many_cached(
+            &cache,
+            &pool,
+            keys,
+            MAX_TOKENS,
+            refill_interval,
+            REFILL_RATE,
+            Utc::now(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(InternalRateLimitError::Pool(_))));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_namespace() {
+        let cfg = Config::from_url(REDIS_URI);
+        let pool = Arc::new(cfg.create_pool(Some(Runtime::Tokio1)).unwrap());
+        let key = Uuid::new_v4().to_string();
+
+        let limiter = |namespace: &str| {
+            RateLimiter::new(pool.clone(), RateLimiterConfig {
+                namespace: Some(namespace.to_owned()),
+                max_tokens: MAX_TOKENS,
+                interval: chrono::Duration::try_milliseconds(REFILL_INTERVAL_MILLIS).unwrap(),
+                refill_rate: REFILL_RATE,
+                burst: None,
+            })
+        };
+        let limiter_a = limiter("a");
+        let limiter_b = limiter("b");
+
+        let namespaced_keys = [format!("a:{key}"), format!("b:{key}")];
+
+        // Before running the test, ensure the test keys are cleared
+        redis_clear_keys(REDIS_URI, &namespaced_keys).await;
+
+        for i in 1..=MAX_TOKENS {
+            let result = limiter_a.check(&key).await.unwrap();
+            assert_eq!(result.remaining, (MAX_TOKENS - i) as u64);
+        }
+        assert!(limiter_a.check(&key).await.is_err());
+
+        // The key of the other limiter is unaffected.
+        let result = limiter_b.check(&key).await.unwrap();
+        assert_eq!(result.remaining, (MAX_TOKENS - 1) as u64);
+
+        let result = limiter_b.check_many(&[&key]).await.unwrap();
+        assert_eq!(result[&key].0, (MAX_TOKENS - 2) as i64);
+
+        // Clear keys after the test
+        redis_clear_keys(REDIS_URI, &namespaced_keys).await;
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_burst() {
+        const BURST: u32 = 8;
+
+        let cfg = Config::from_url(REDIS_URI);
+        let pool = Arc::new(cfg.create_pool(Some(Runtime::Tokio1)).unwrap());
+        let keys = [Uuid::new_v4().to_string(), Uuid::new_v4().to_string()];
+
+        let limiter = RateLimiter::new(pool, RateLimiterConfig {
+            namespace: None,
+            max_tokens: MAX_TOKENS,
+            interval: chrono::Duration::try_milliseconds(REFILL_INTERVAL_MILLIS).unwrap(),
+            refill_rate: REFILL_RATE,
+            burst: Some(BURST),
+        });
+
+        // Before running the test, ensure the test keys are cleared
+        redis_clear_keys(REDIS_URI, &keys).await;
+
+        for i in 1..=BURST {
+            let result = limiter.check(&keys[0]).await.unwrap();
+            assert_eq!(result.remaining, (BURST - i) as u64);
+        }
+        assert!(limiter.check(&keys[0]).await.is_err());
+
+        let result = limiter.check_many(&[&keys[1]]).await.unwrap();
+        assert_eq!(result[&keys[1]].0, (BURST - 1) as i64);
+
+        // Clear keys after the test
+        redis_clear_keys(REDIS_URI, &keys).await;
+    }
+
+    #[tokio::test]
+    async fn test_sliding_window_many() {
+        let cfg = Config::from_url(REDIS_URI);
+        let pool = Arc::new(cfg.create_pool(Some(Runtime::Tokio1)).unwrap());
+        let key = Uuid::new_v4().to_string();
+
+        // Before running the test, ensure the test keys are cleared
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+
+        let window = chrono::Duration::try_milliseconds(REFILL_INTERVAL_MILLIS).unwrap();
+        let rate_limit = |now_millis| {
+            let key = key.clone();
+            let pool = pool.clone();
+            async move {
+                sliding_window_many(&pool, vec![key.clone()], MAX_TOKENS, window, now_millis)
+                    .await
+                    .unwrap()
+                    .get(&key)
+                    .unwrap()
+                    .to_owned()
+            }
+        };
+
+        let started_at = Utc::now();
+        let reset = (started_at.timestamp_millis() + REFILL_INTERVAL_MILLIS) as u64;
+
+        for count in 1..=MAX_TOKENS {
+            assert_eq!(rate_limit(started_at).await, (count as i64, reset));
+        }
+        assert_eq!(rate_limit(started_at).await, (-1, reset));
+
+        // Halfway through the window the limit is still in place.
+        let now = started_at + window / 2;
+        assert_eq!(rate_limit(now).await, (-1, reset));
+
+        // The requests outside of the window are forgiven.
+        let now = started_at + window;
+        let reset = (now.timestamp_millis() + REFILL_INTERVAL_MILLIS) as u64;
+        assert_eq!(rate_limit(now).await, (1, reset));
+
+        // Clear keys after the test
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+    }
+
+    #[tokio::test]
+    async fn test_sliding_window() {
+        // Create Moka cache with a TTL of the window
+        let cache: Cache<String, u64> = Cache::builder()
+            .time_to_live(std::time::Duration::from_millis(
+                REFILL_INTERVAL_MILLIS as u64,
+            ))
+            .build();
+
+        let cfg = Config::from_url(REDIS_URI);
+        let pool = Arc::new(cfg.create_pool(Some(Runtime::Tokio1)).unwrap());
+        let key = Uuid::new_v4().to_string();
+
+        // Before running the test, ensure the test keys are cleared
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+
+        let window = chrono::Duration::try_milliseconds(REFILL_INTERVAL_MILLIS).unwrap();
+        let rate_limit = || {
+            let key = key.clone();
+            let pool = pool.clone();
+            let cache = cache.clone();
+            async move { sliding_window(&cache, &pool, key, MAX_TOKENS, window, Utc::now()).await }
+        };
+
+        for (i, remaining) in (0..MAX_TOKENS).rev().enumerate() {
+            assert_eq!(
+                rate_limit().await.unwrap().remaining,
+                remaining as u64,
+                "{i}"
+            );
+        }
+        assert!(rate_limit()
+            .await
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("Rate limit exceeded"));
+
+        // Sleep until the window passes and try again
+        sleep(window.to_std().unwrap() * 2).await;
+        assert!(rate_limit().await.is_ok());
+
+        // Clear keys after the test
+        redis_clear_keys(REDIS_URI, &[key.clone()]).await;
+    }
 }
@@ -0,0 +1,35 @@
+-- Sliding window log: every accepted request is stored in a sorted set scored by its timestamp.
+local keys = KEYS -- identifier including prefixes
+local maxRequests = tonumber(ARGV[1]) -- maximum number of requests within the window
+local window = tonumber(ARGV[2]) -- size of the window in milliseconds
+local now = tonumber(ARGV[3]) -- current timestamp in milliseconds
+
+local results = {}
+
+for i, key in ipairs(keys) do
+    -- Forget the requests which are outside of the window.
+    redis.call("ZREMRANGEBYSCORE", key, "-inf", now - window)
+
+    local count = redis.call("ZCARD", key)
+
+    if count < maxRequests then
+        -- The count makes the member unique for requests with the same timestamp.
+        redis.call("ZADD", key, now, now .. ":" .. count)
+        redis.call("PEXPIRE", key, window)
+        count = count + 1
+    else
+        count = -1
+    end
+
+    local reset = now + window
+    local oldest = redis.call("ZRANGE", key, 0, 0, "WITHSCORES")
+
+    if oldest[2] then
+        reset = tonumber(oldest[2]) + window
+    end
+
+    results[key] = {count, reset}
+end
+
+-- Redis doesn't support Lua table responses: https://stackoverflow.com/a/24302613
+return cjson.encode(results)
@@ -4,6 +4,7 @@ local maxTokens = tonumber(ARGV[1]) -- maximum number of tokens
 local interval = tonumber(ARGV[2]) -- size of the window in milliseconds
 local refillRate = tonumber(ARGV[3]) -- how many tokens are refilled after each interval
 local now = tonumber(ARGV[4]) -- current timestamp in milliseconds
+local burst = tonumber(ARGV[5]) or maxTokens -- number of tokens in a new bucket
 
 local results = {}
 
@@ -15,7 +16,7 @@ for i, key in ipairs(keys) do
 
     if bucket[1] == false then
         refilledAt = now
-        tokens = maxTokens
+        tokens = burst
     else
         refilledAt = tonumber(bucket[1])
         tokens = tonumber(bucket[2])
@@ -23,7 +24,10 @@ for i, key in ipairs(keys) do
 
     if now >= refilledAt + interval then
         local numRefills = math.floor((now - refilledAt) / interval)
-        tokens = math.min(maxTokens, tokens + numRefills * refillRate)
+        -- Tokens left over from the burst are kept, but not refilled.
+        if tokens < maxTokens then
+            tokens = math.min(maxTokens, tokens + numRefills * refillRate)
+        end
 
         refilledAt = refilledAt + numRefills * interval
     end
@@ -32,7 +36,8 @@ for i, key in ipairs(keys) do
         results[key] = {-1, refilledAt + interval}
     else
         local remaining = tokens - 1
-        local expireAt = math.ceil(((maxTokens - remaining) / refillRate)) * interval
+        -- The bucket expires, restoring the burst, once it would be refilled to it.
+        local expireAt = math.ceil(((burst - remaining) / refillRate)) * interval
 
         redis.call("HSET", key, "refilledAt", refilledAt, "tokens", remaining)
         redis.call("PEXPIRE", key, expireAt)